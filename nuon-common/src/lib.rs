@@ -109,6 +109,8 @@ pub enum InnerMessage {
     LoadUsernamePass(String, String),
     /// Add a new mark at the current position.
     Mark(u8),
+    /// Response to Mark with the position of the new mark, so that the UI can save it.
+    MarkPosition(u8, Position),
     /// Open the given URL.
     /// This is used when starting a new nuon process to tell the existing process to open a
     /// new window.
@@ -129,12 +131,26 @@ pub enum InnerMessage {
     ScrollToPercent(u32),
     /// Set the selected file on a file input.
     SelectFile(String),
+    /// Replace the marks of the page by the specified marks and positions.
+    SetMarks(Vec<(u8, Position)>),
     /// Show the hints over the elements.
     ShowHints(String),
     /// Submit the login form.
     SubmitLoginForm(),
 }
 
+/// The position of a mark in a web page.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Position {
+    /// The CSS selector of the element at the top of the viewport, if any.
+    /// It starts from the nearest element having an id.
+    pub anchor: Option<String>,
+    /// The offset in pixels of the top of the viewport from the top of the anchor element.
+    pub offset: i64,
+    /// The scroll percentage, used when the anchor element does not exist anymore.
+    pub percentage: u32,
+}
+
 /// Either all the page is shown (hence, no percentage) or a value between 0 and 100.
 #[derive(Clone, Copy, Debug, RustcEncodable, RustcDecodable)]
 pub enum Percentage {
@@ -137,6 +137,34 @@ pub fn click(element: &DOMElement, ctrl_key: bool) {
     mouse_event("click", element, ctrl_key);
 }
 
+/// Get a CSS selector matching the element, starting from its nearest ancestor having an id.
+pub fn css_path(element: &DOMElement) -> Option<String> {
+    let mut path = vec![];
+    let mut element = Some(element.clone());
+    while let Some(el) = element {
+        if let Some(id) = el.id().filter(|id| is_css_identifier(id)) {
+            path.push(format!("#{}", id));
+            break;
+        }
+        let tag_name = el.tag_name()?.to_lowercase();
+        let parent = el.parent_element();
+        if parent.is_none() {
+            path.push(tag_name);
+            break;
+        }
+        let mut index = 1;
+        let mut sibling = el.previous_element_sibling();
+        while let Some(sib) = sibling {
+            index += 1;
+            sibling = sib.previous_element_sibling();
+        }
+        path.push(format!("{}:nth-child({})", tag_name, index));
+        element = parent;
+    }
+    path.reverse();
+    Some(path.join(" > "))
+}
+
 /// Get the body element of the web page.
 pub fn get_body(page: &WebPage) -> Option<DOMHTMLElement> {
     page.dom_document().and_then(|document|
@@ -288,6 +316,23 @@ pub fn is_enabled(element: &DOMElement) -> bool {
     true
 }
 
+/// Check if an element or one of its ancestors stays at the same place in the viewport when
+/// scrolling.
+pub fn is_fixed(element: &DOMElement) -> bool {
+    let document = unwrap_opt_or_ret!(element.owner_document(), false);
+    let window = unwrap_opt_or_ret!(document.default_view(), false);
+    let mut element = Some(element.clone());
+    while let Some(el) = element {
+        let style = unwrap_opt_or_ret!(window.computed_style(&el, None), false);
+        let position = DOMCSSStyleDeclarationExt::property_value(&style, "position").map(Into::into);
+        if position == Some("fixed".to_string()) || position == Some("sticky".to_string()) {
+            return true;
+        }
+        element = el.parent_element();
+    }
+    false
+}
+
 /// Check if an element is hidden.
 /// This is not exactly the opposite as `is_visible` since `is_hidden` returns false for elements that
 /// are visible, but outside the viewport.
@@ -323,6 +368,12 @@ pub fn is_text_input(element: &DOMElement) -> bool {
     }
 }
 
+/// Check if an id can be used as is in a CSS selector.
+fn is_css_identifier(id: &str) -> bool {
+    let starts_with_digit = id.chars().next().map(|char| char.is_ascii_digit()).unwrap_or(true);
+    !starts_with_digit && id.chars().all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+}
+
 /// Check if an element is visible and in the viewport.
 pub fn is_visible(document: &DOMDocument, element: &DOMElement) -> bool {
     let window = unwrap_opt_or_ret!(document.default_view(), false);
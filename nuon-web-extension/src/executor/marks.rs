@@ -19,30 +19,96 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
+use webkit2gtk_webextension::traits::{
+    DOMClientRectExt,
+    DOMDocumentExt,
+    DOMDOMWindowExt,
+    DOMElementExt,
+    WebPageExt,
+};
+
+use nuon_common::InnerMessage::MarkPosition;
+use nuon_common::LAST_MARK;
 use nuon_common::Percentage::{All, Percent};
+use nuon_common::Position;
 
+use dom::{css_path, is_fixed};
 use executor::Executor;
 
 impl Executor {
-    pub fn add_mark(&mut self, char: u8) {
+    pub fn add_mark(&mut self, char: u8) -> Position {
         let percentage = self.scroll_percentage();
         let percentage =
             match percentage {
                 All => 0,
-                Percent(percent) => percent,
+                Percent(percent) => percent as u32,
+            };
+        let (anchor, offset) =
+            match self.top_anchor() {
+                Some((anchor, offset)) => (Some(anchor), offset),
+                None => (None, 0),
             };
-        self.model.marks.insert(char, percentage as u32);
+        let position = Position {
+            anchor,
+            offset,
+            percentage,
+        };
+        self.model.marks.insert(char, position.clone());
+        position
     }
 
+    /// Go to the element of the mark if it still exists, otherwise go to its percentage.
     pub fn go_to_mark(&mut self, mark: u8) {
-        if let Some(&percent) = self.model.marks.get(&mark) {
-            self.scroll_to_percent(percent);
+        if let Some(position) = self.model.marks.get(&mark).cloned() {
+            if !self.scroll_to_anchor(&position) {
+                self.scroll_to_percent(position.percentage);
+            }
         }
         // TODO: warn if the mark does not exist?
     }
 
+    /// Add a mark requested by the user and send its position to the UI process to save it.
+    pub fn mark(&mut self, char: u8) {
+        let position = self.add_mark(char);
+        self.send(MarkPosition(char, position));
+    }
+
     pub fn reset_marks(&mut self) {
         self.model.marks.clear();
-        // TODO: reset the marks on page load.
+    }
+
+    /// Scroll so that the anchor element of the mark is at the same offset from the top of the
+    /// viewport as when the mark was added.
+    /// Returns false if the anchor element does not exist.
+    fn scroll_to_anchor(&mut self, position: &Position) -> bool {
+        let anchor = unwrap_opt_or_ret!(position.anchor.as_ref(), false);
+        let document = unwrap_opt_or_ret!(self.model.page.dom_document(), false);
+        let element = unwrap_opt_or_ret!(document.query_selector(anchor).ok().flatten(), false);
+        let rect = unwrap_opt_or_ret!(element.bounding_client_rect(), false);
+        let window = unwrap_opt_or_ret!(document.default_view(), false);
+        self.add_mark(LAST_MARK);
+        window.scroll_by(0.0, rect.top() as f64 + position.offset as f64);
+        true
+    }
+
+    /// Replace the marks by those saved by the UI process for this page.
+    pub fn set_marks(&mut self, marks: Vec<(u8, Position)>) {
+        self.model.marks = marks.into_iter().collect();
+    }
+
+    /// Get the CSS path of the element at the top of the viewport and the offset of the viewport
+    /// from the top of this element.
+    /// Returns None when this element is fixed, in which case the mark only uses the percentage.
+    fn top_anchor(&self) -> Option<(String, i64)> {
+        let document = self.model.page.dom_document()?;
+        let window = document.default_view()?;
+        let element = document.element_from_point(window.inner_width() / 2, 0)?;
+        // A fixed element, like a header, does not move with the content.
+        if is_fixed(&element) {
+            return None;
+        }
+        let anchor = css_path(&element)?;
+        let rect = element.bounding_client_rect()?;
+        Some((anchor, -rect.top() as i64))
     }
 }
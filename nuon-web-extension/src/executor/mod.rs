@@ -63,7 +63,7 @@ use webkit2gtk_webextension::{
     WebPage,
 };
 
-use nuon_common::{FollowMode, InnerMessage, protocol::encode};
+use nuon_common::{FollowMode, InnerMessage, Position, protocol::encode};
 use nuon_common::Action::{
     self,
     CopyLink,
@@ -102,7 +102,7 @@ pub struct Model {
     hint_keys: String,
     hint_map: HashMap<String, DOMElement>,
     last_hovered_element: Option<DOMElement>,
-    marks: HashMap<u8, u32>, // Byte to percent.
+    marks: HashMap<u8, Position>,
     page: WebPage,
     relm: Relm<Executor>,
     scroll_element: Option<DOMElement>,
@@ -169,7 +169,7 @@ impl Update for Executor {
                     HideHints() => self.hide_hints(),
                     InsertText(text) => self.insert_text(&text),
                     LoadUsernamePass(username, password) => self.load_username_pass(&username, &password),
-                    Mark(char) => self.add_mark(char),
+                    Mark(char) => self.mark(char),
                     ResetMarks() => self.reset_marks(),
                     ResetScrollElement() => self.reset_scroll_element(),
                     ScrollBy(pixels) => self.scroll_by(pixels),
@@ -177,6 +177,7 @@ impl Update for Executor {
                     ScrollTop() => self.scroll_top(),
                     ScrollToPercent(percent) => self.scroll_to_percent(percent),
                     SelectFile(file) => self.select_file(&file),
+                    SetMarks(marks) => self.set_marks(marks),
                     ShowHints(hint_chars) => self.show_hints(&hint_chars),
                     SubmitLoginForm() => self.submit_login_form(),
                     _ => warn!("Unexpected message received: {:?}", msg),
@@ -66,7 +66,7 @@ impl App {
             "https://pgl.yoyo.org/adservers/serverlist.php?hostformat=hosts&mimetype=plaintext",
         ];
         for url in &urls {
-            if let Some(download) = self.widgets.webview.download_uri(url) {
+            if let Some(download) = self.webview().download_uri(url) {
                 let suggested_filename =
                     download.response()
                         .and_then(|response| response.suggested_filename().map(Into::into))
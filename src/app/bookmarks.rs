@@ -21,6 +21,8 @@
 
 //! Bookmark management in the application.
 
+use std::path::Path;
+
 use mg::{
     CustomDialog,
     DialogBuilder,
@@ -31,13 +33,15 @@ use mg::{
 use webkit2gtk::WebViewExt;
 
 use app::{App, TAG_COMPLETER};
+use bookmark_export;
+use bookmark_import;
 use app::Msg::TagEdit;
 
 impl App {
     /// Add the current page to the bookmarks.
     pub fn bookmark(&self) {
-        if let Some(url) = self.widgets.webview.uri() {
-            let title = self.widgets.webview.title();
+        if let Some(url) = self.webview().uri() {
+            let title = self.webview().title();
             let message = format!("Added bookmark: {}", url);
             match self.model.bookmark_manager.add(url.into(), title.map(Into::into)) {
                 Ok(true) => self.components.mg.emit(Info(message)),
@@ -49,7 +53,7 @@ impl App {
 
     /// Delete the current page from the bookmarks.
     pub fn delete_bookmark(&self) {
-        if let Some(url) = self.widgets.webview.uri() {
+        if let Some(url) = self.webview().uri() {
             match self.model.bookmark_manager.delete(&url) {
                 Ok(true) => self.components.mg.emit(Info(format!("Deleted bookmark: {}", url))),
                 Ok(false) => self.info_page_not_in_bookmarks(),
@@ -89,24 +93,57 @@ impl App {
 
     /// Edit the tags of the current page from the bookmarks.
     pub fn edit_bookmark_tags(&self) {
-        if self.model.bookmark_manager.exists(&self.model.current_url) {
-            match self.model.bookmark_manager.get_tags(&self.model.current_url) {
-                Ok(tags) => {
-                    let default_answer = tags.join(", ");
-                    let responder = Box::new(InputDialog::new(&self.model.relm, TagEdit));
+        let tags = self.model.bookmark_manager.exists(&self.model.current_url)
+            .and_then(|exists|
+                if exists {
+                    self.model.bookmark_manager.get_tags(&self.model.current_url).map(Some)
+                }
+                else {
+                    Ok(None)
+                }
+            );
+        match tags {
+            Ok(Some(tags)) => {
+                let default_answer = tags.join(", ");
+                let responder = Box::new(InputDialog::new(&self.model.relm, TagEdit));
 
-                    let builder = DialogBuilder::new()
-                        .completer(TAG_COMPLETER)
-                        .default_answer(default_answer)
-                        .message("Bookmark tags (separated by comma):".to_string())
-                        .responder(responder);
-                    self.components.mg.emit(CustomDialog(builder));
-                },
-                Err(err) => self.error(&err.to_string()),
-            }
+                let builder = DialogBuilder::new()
+                    .completer(TAG_COMPLETER)
+                    .default_answer(default_answer)
+                    .message("Bookmark tags (separated by comma):".to_string())
+                    .responder(responder);
+                self.components.mg.emit(CustomDialog(builder));
+            },
+            Ok(None) => self.info_page_not_in_bookmarks(),
+            Err(err) => self.error(&err.to_string()),
         }
-        else {
-            self.info_page_not_in_bookmarks();
+    }
+
+    /// Export the bookmarks to a file.
+    pub fn export_bookmarks(&self, path: &str) {
+        let result = self.model.bookmark_manager.export()
+            .and_then(|bookmarks| {
+                let count = bookmarks.len();
+                bookmark_export::export(Path::new(path), bookmarks)?;
+                Ok(count)
+            });
+        match result {
+            Ok(count) => self.components.mg.emit(Info(format!("Exported {} bookmarks to {}", count, path))),
+            Err(err) => self.error(&err.to_string()),
+        }
+    }
+
+    /// Import the bookmarks from a file exported by another browser.
+    pub fn import_bookmarks(&self, path: &str) {
+        let result = bookmark_import::import(Path::new(path))
+            .and_then(|bookmarks| {
+                let added_count = self.model.bookmark_manager.import(&bookmarks)?;
+                Ok((bookmarks.len(), added_count))
+            });
+        match result {
+            Ok((count, added_count)) =>
+                self.components.mg.emit(Info(format!("Imported {} bookmarks ({} new)", count, added_count))),
+            Err(err) => self.error(&err.to_string()),
         }
     }
 
@@ -23,19 +23,91 @@
 
 use mg::Variables;
 use mg::DefaultConfig::{self, Dir, File};
+use mg_settings::Command::{App as ApplicationCommand, Custom, Map, Set, Unmap};
+use mg_settings::settings::Settings;
 use webkit2gtk::WebViewExt;
 
 use config_dir::ConfigDir;
-use super::App;
+use config_file::{ConfigCommand, load_config};
+use settings::{AppSettings, AppSettingsVariant, PrivateContext, TabBar};
+use settings::AppSettingsVariant::{ConfigWatch, HomePage};
+use super::{App, DEFAULT_HINT_CHARS};
+use super::tabs::current_webview;
+use webview::Msg::{AddStylesheets, ApplySiteSettings, ReloadScripts, ResetSettings};
 
 impl App {
     /// Create the variables accessible from the config files.
     pub fn create_variables(&mut self) {
-        let webview = self.widgets.webview.clone();
+        let notebook = self.widgets.notebook.clone();
         self.components.mg.emit(Variables(vec![("url", Box::new(move || {
-            webview.uri().map(Into::into).unwrap_or_default()
+            current_webview(&notebook)
+                .and_then(|webview| webview.uri())
+                .map(Into::into)
+                .unwrap_or_default()
         }))]));
     }
+
+    /// Reload the configuration files and apply them to the window and its web views.
+    /// The key mappings are only updated in the windows opened after the reload, which is told to
+    /// the user.
+    pub fn reload_config(&mut self) {
+        let config = load_config(&self.model.config_dir.config_home());
+
+        // Forget what the previous config commands added, since they are run again.
+        self.model.default_search_engine = None;
+        self.model.search_engines.clear();
+        self.model.site_privacy.clear();
+        self.model.site_settings.clear();
+        self.model.user_agents.clear();
+        self.reset_settings();
+        let mut has_key_mappings = false;
+        for ConfigCommand { command, .. } in config.commands {
+            match command {
+                Custom(command) => self.handle_command(&command),
+                Set(name, value) =>
+                    match AppSettings::to_variant(&name, value) {
+                        // Only change the home page, to avoid opening it in the current tab.
+                        Ok(HomePage(url)) => self.model.home_page = Some(url),
+                        Ok(setting) => self.setting_changed(setting),
+                        Err(_) => (), // Already reported by load_config().
+                    },
+                // mg cannot change the key mappings of an existing window.
+                Map { .. } | Unmap { .. } => has_key_mappings = true,
+                ApplicationCommand(_) => (),
+            }
+        }
+        for tab in &self.model.tabs {
+            tab.emit(AddStylesheets);
+            tab.emit(ReloadScripts);
+            tab.emit(ApplySiteSettings);
+        }
+
+        for error in &config.errors {
+            error!("{}", error);
+        }
+        match config.errors.len() {
+            0 if has_key_mappings =>
+                self.info("Configuration reloaded (the key mappings only apply to the new windows)".to_string()),
+            0 => self.info("Configuration reloaded".to_string()),
+            1 => self.error(&config.errors[0].to_string()),
+            count => self.error(&format!("{} (and {} other errors)", config.errors[0], count - 1)),
+        }
+    }
+
+    /// Reset the settings to their value in a new window, so that the settings removed from the
+    /// config files do not keep their previous value.
+    fn reset_settings(&mut self) {
+        self.model.hint_chars = DEFAULT_HINT_CHARS.to_string();
+        self.model.home_page = None;
+        self.model.open_in_tab = false;
+        self.model.webview_settings.clear();
+        for tab in &self.model.tabs {
+            tab.emit(ResetSettings);
+        }
+        self.setting_changed(ConfigWatch(false));
+        self.setting_changed(AppSettingsVariant::PrivateContext(PrivateContext::default()));
+        self.setting_changed(AppSettingsVariant::TabBar(TabBar::default()));
+    }
 }
 
 /// Get the default configuration files and directories.
@@ -48,6 +120,7 @@ pub fn default_config(config_dir: &ConfigDir) -> Vec<DefaultConfig> {
     let config_path = config_dir.config_file("config");
     let keys_path = config_dir.config_file("keys");
     let webkit_config_path = config_dir.config_file("webkit");
+    let marks_path = config_dir.config_file("marks");
     let hints_css_path = config_dir.config_file("stylesheets/hints.css");
     let (popup_whitelist_path, popup_blacklist_path) = App::popup_path(config_dir);
     let (permission_whitelist_path, permission_blacklist_path) = App::permission_path(config_dir);
@@ -60,6 +133,7 @@ pub fn default_config(config_dir: &ConfigDir) -> Vec<DefaultConfig> {
          File(keys_path, include_str!("../../config/keys")),
          File(config_path, include_str!("../../config/config")),
          File(webkit_config_path, include_str!("../../config/webkit")),
+         File(marks_path, include_str!("../../config/marks")),
          File(hints_css_path, include_str!("../../config/stylesheets/hints.css")),
          File(popup_whitelist_path, ""),
          File(popup_blacklist_path, ""),
@@ -67,3 +141,42 @@ pub fn default_config(config_dir: &ConfigDir) -> Vec<DefaultConfig> {
          File(permission_blacklist_path, ""),
         ]
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, create_dir_all};
+
+    use mg::DefaultConfig::{Dir, File};
+    use tempfile::Builder as TempFileBuilder;
+
+    use config_check::check_config;
+    use config_dir::ConfigDir;
+    use super::default_config;
+
+    #[test]
+    fn generated_default_config() {
+        let temp_dir = TempFileBuilder::new().tempdir().unwrap();
+        let config_dir = ConfigDir::new(&Some(temp_dir.path().to_string_lossy().into_owned()), &None).unwrap();
+        for config in default_config(&config_dir) {
+            match config {
+                Dir(path) => create_dir_all(path.unwrap()).unwrap(),
+                File(path, content) => {
+                    let path = path.unwrap();
+                    create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(path, content).unwrap();
+                },
+            }
+        }
+
+        let config_home = config_dir.config_home();
+        for filename in &["config", "keys", "marks", "webkit", "stylesheets/hints.css"] {
+            let expected = fs::read_to_string(format!("{}/config/{}", env!("CARGO_MANIFEST_DIR"), filename)).unwrap();
+            assert_eq!(fs::read_to_string(config_home.join(filename)).unwrap(), expected, "{}", filename);
+        }
+
+        let errors: Vec<_> = check_config(&config_home).iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(errors, Vec::<String>::new());
+    }
+}
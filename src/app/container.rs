@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Containers, which are windows with their own cookies and website data.
+
+use webkit2gtk::{self, WebViewExt};
+
+use app::App;
+use app::Msg::CreateWindow;
+use config_dir::is_valid_name;
+use message_server::Privacy;
+
+impl App {
+    /// Pin a domain and its subdomains to a container.
+    pub fn add_container_domain(&mut self, args: &str) {
+        let args: Vec<_> = args.split_whitespace().collect();
+        if args.len() == 2 && is_valid_name(args[0]) {
+            self.model.site_privacy.add_container_domain(args[1], args[0]);
+        }
+        else if args.len() == 2 {
+            self.error(&format!("container-domain: invalid container name {}", args[0]));
+        }
+        else {
+            self.error(&format!("container-domain: expecting 2 arguments, got {} arguments", args.len()));
+        }
+    }
+
+    /// Open an URL in a new window of a container.
+    pub fn container_open(&self, args: &str) {
+        let args: Vec<_> = args.splitn(2, ' ').map(str::trim).collect();
+        if args.len() != 2 || args[1].is_empty() {
+            self.error("container-open: expecting a container name and an URL");
+        }
+        else if !is_valid_name(args[0]) {
+            self.error(&format!("container-open: invalid container name {}", args[0]));
+        }
+        else {
+            let url = self.transform_url(args[1]);
+            self.model.relm.stream().emit(CreateWindow(url, Privacy::Container(args[0].to_string())));
+        }
+    }
+
+    /// Open in another window an URL whose navigation was stopped because it is a private site or
+    /// is pinned to another container.
+    pub fn redirect(&self, url: &str, privacy: Privacy) {
+        if privacy == Privacy::Private {
+            self.info(format!("Opened {} in a private window since it is in the private site list", url));
+        }
+        self.model.relm.stream().emit(CreateWindow(url.to_string(), privacy));
+    }
+
+    /// Move the page being loaded in the web view to a private window or to its container, if it
+    /// is pinned to another one.
+    /// The web views already stop most navigations before sending their request, but the
+    /// navigations started by scripts and the redirections of the pages opened from the command
+    /// line can only be detected once they are loading.
+    /// Returns true if the page was moved.
+    pub fn redirect_navigation(&self, webview: &webkit2gtk::WebView) -> bool {
+        if let Some(url) = webview.uri() {
+            if let Some(privacy) = self.model.site_privacy.redirected_privacy(&url) {
+                webview.stop_loading();
+                self.redirect(&url, privacy);
+                return true;
+            }
+        }
+        false
+    }
+}
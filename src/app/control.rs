@@ -0,0 +1,66 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Answers to the queries of the control socket that need the web view.
+
+use serde_json::Value;
+use webkit2gtk::WebViewExt;
+
+use app::App;
+use app::Msg::ControlReply;
+use control_server::ControlQuery;
+
+/// The script getting the text selected in the page.
+const SELECTION_SCRIPT: &str = "window.getSelection().toString()";
+
+impl App {
+    /// Answer a query of a control client.
+    pub fn answer_control_query(&mut self, client: usize, id: Value, query: ControlQuery) {
+        match query {
+            ControlQuery::Selection => self.send_selection(client, id),
+            ControlQuery::WaitLoad => {
+                if self.webview().is_loading() {
+                    self.model.load_waiters.push((client, id));
+                }
+                else {
+                    self.send_loaded_url(client, id);
+                }
+            },
+        }
+    }
+
+    /// Answer the clients waiting for the current page to be loaded.
+    pub fn answer_load_waiters(&mut self) {
+        let waiters: Vec<_> = self.model.load_waiters.drain(..).collect();
+        for (client, id) in waiters {
+            self.send_loaded_url(client, id);
+        }
+    }
+
+    fn send_loaded_url(&self, client: usize, id: Value) {
+        let url = self.webview().uri().map(|url| url.to_string()).unwrap_or_default();
+        self.model.relm.stream().emit(ControlReply(client, id, Ok(Value::String(url))));
+    }
+
+    fn send_selection(&self, client: usize, id: Value) {
+        self.run_script(SELECTION_SCRIPT, move |selection| ControlReply(client, id, Ok(Value::String(selection))));
+    }
+}
@@ -24,6 +24,7 @@
 use std::fs::{read_dir, remove_file};
 use std::path::{Path, PathBuf};
 
+use gtk::prelude::NotebookExtManual;
 use mg::yes_no_question;
 use mg::DialogResult::{self, Answer, Shortcut};
 use relm::StreamHandle;
@@ -97,10 +98,11 @@ impl App {
         if let Some(context) = self.get_webview_context() {
             let stream = self.model.relm.stream().clone();
             let list_stream = self.streams.download_list_view.clone();
-            let webview = self.widgets.webview.clone();
+            let notebook = self.widgets.notebook.clone();
             connect!(context, connect_download_started(_, download), self.streams.download_list_view, {
                 if let Some(download_web_view) = download.web_view() {
-                    if download_web_view == webview {
+                    // Only handle the downloads started from a tab of this window.
+                    if notebook.page_num(&download_web_view).is_some() {
                         Self::handle_decide_destination(&stream, &list_stream, download);
                         Some(Add(download.clone()))
                     }
@@ -141,7 +143,7 @@ impl App {
     }
 
     pub fn download_link(&self, url: &str) {
-        self.widgets.webview.download_uri(url);
+        self.webview().download_uri(url);
     }
 
     fn handle_decide_destination(stream: &StreamHandle<app::Msg>, list_stream: &StreamHandle<download_list_view::Msg>,
@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Browsing history management in the application.
+
+use webkit2gtk::{self, WebViewExt};
+
+use app::App;
+
+impl App {
+    /// Add the page of the web view to the browsing history.
+    /// Nothing is recorded in private windows.
+    pub fn add_to_history(&self, webview: &webkit2gtk::WebView) {
+        if webview.is_ephemeral() {
+            return;
+        }
+
+        if let Some(url) = webview.uri() {
+            let title = webview.title().map(Into::into);
+            handle_error!(self.model.bookmark_manager.add_visit(&url, title));
+        }
+    }
+
+    /// Save the title of the page of the web view in the browsing history.
+    pub fn update_history_title(&self, webview: &webkit2gtk::WebView) {
+        if webview.is_ephemeral() {
+            return;
+        }
+
+        if let (Some(url), Some(title)) = (webview.uri(), webview.title()) {
+            handle_error!(self.model.bookmark_manager.update_history_title(&url, &title));
+        }
+    }
+}
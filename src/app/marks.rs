@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Persistent and global scroll marks.
+//!
+//! The lowercase marks are saved per page while the uppercase marks are global: they record the
+//! page and go to it when used from another page.
+
+use mg::Info;
+use nuon_common::InnerMessage::{GoToMark, Mark, SetMarks};
+use nuon_common::{LAST_MARK, Position};
+use webkit2gtk::{self, WebViewExt};
+
+use app::App;
+use webview::Msg::PageOpen;
+
+impl App {
+    /// Add a mark at the current position of the page.
+    pub fn add_mark(&mut self, mark: &str) {
+        let mark = mark_from_str(mark);
+        self.server_send(Mark(mark));
+        self.components.mg.emit(Info(format!("Added mark {}", mark as char)));
+    }
+
+    /// Go to a mark.
+    /// A global mark pointing to another page opens this page in the current tab and goes to the
+    /// mark once it is loaded.
+    pub fn go_to_mark(&mut self, mark: &str) {
+        let mark = mark_from_str(mark);
+        if is_global_mark(mark) {
+            match self.model.bookmark_manager.global_mark_url(mark) {
+                Ok(Some(url)) => {
+                    if url != self.model.current_url {
+                        self.model.pending_mark = Some(mark);
+                        self.current_tab().emit(PageOpen(url));
+                        return;
+                    }
+                },
+                Ok(None) => {
+                    self.error(&format!("Mark {} is not set", mark as char));
+                    return;
+                },
+                Err(error) => {
+                    self.error(&error.to_string());
+                    return;
+                },
+            }
+        }
+        self.server_send(GoToMark(mark));
+    }
+
+    /// Save the position of a mark sent by the web process.
+    /// Nothing is saved in private windows.
+    pub fn save_mark(&mut self, mark: u8, position: Position) {
+        if self.webview().is_ephemeral() || mark == LAST_MARK {
+            return;
+        }
+
+        let url = self.model.current_url.clone();
+        if is_global_mark(mark) {
+            handle_error!(self.model.bookmark_manager.add_global_mark(mark, &url, &position));
+        }
+        else {
+            handle_error!(self.model.bookmark_manager.add_mark(&url, mark, &position));
+        }
+    }
+
+    /// Send the saved marks of the page to the web process of the web view once it is loaded.
+    /// Then, go to the pending global mark, if any.
+    pub fn send_marks(&mut self, webview: &webkit2gtk::WebView) {
+        if let Some(url) = webview.uri() {
+            match self.model.bookmark_manager.marks(&url) {
+                Ok(marks) => self.server_send_to(webview, SetMarks(marks)),
+                Err(error) => self.error(&error.to_string()),
+            }
+        }
+
+        if webview == self.webview() {
+            if let Some(mark) = self.model.pending_mark.take() {
+                self.server_send_to(webview, GoToMark(mark));
+            }
+        }
+    }
+}
+
+/// Check if the mark is an uppercase, global, mark.
+fn is_global_mark(mark: u8) -> bool {
+    mark.is_ascii_uppercase()
+}
+
+fn mark_from_str(mark: &str) -> u8 {
+    mark.as_bytes().first().cloned().unwrap_or(LAST_MARK)
+}
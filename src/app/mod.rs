@@ -30,26 +30,40 @@ mod adblock;
 mod bookmarks;
 mod browser;
 mod config;
+mod container;
+mod control;
 mod copy_paste;
 mod dialog;
 mod download;
 mod file_chooser;
 mod hints;
+mod history;
+mod marks;
 mod pass_filler;
 mod paths;
 mod popup;
+mod private_site;
+mod profile;
+pub mod remote;
 mod search_engine;
+mod session;
 mod server;
+mod site_settings;
+mod spawn;
+mod tabs;
 mod test_utils;
 mod url;
 pub mod user_agent;
+mod windows;
 
 use std::cell::Cell;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::HashMap;
+use std::mem::discriminant;
 use std::rc::Rc;
 
 use gdk::{EventKey, Rectangle};
-use glib::{Cast, ObjectExt};
+use gio::Cancellable;
+use glib::{self, Cast, Continue, MainContext, ObjectExt};
 use gtk::{
     self,
     Inhibit,
@@ -80,8 +94,9 @@ use mg::{
     question,
     yes_no_question, ForegroundColor,
 };
-use relm::{Relm, Widget};
+use relm::{Component, Relm, Widget};
 use relm_derive::widget;
+use serde_json::Value;
 use nuon_common::protocol::decode;
 use webkit2gtk::{
     self,
@@ -99,22 +114,26 @@ use webkit2gtk::{
     WebContext,
     WebViewExt, UserMessageExt, WebContextExt,
 };
-use webkit2gtk::LoadEvent::{self, Started};
+use webkit2gtk::LoadEvent::{self, Committed, Finished, Redirected, Started};
 use webkit2gtk::NavigationType::Other;
 
-use nuon_common::{FollowMode, InnerMessage, LAST_MARK};
+use nuon_common::{FollowMode, InnerMessage};
 use nuon_common::Percentage::{self, All, Percent};
 
 use bookmarks::BookmarkManager;
 use commands::AppCommand;
 use commands::AppCommand::*;
 use completers::{
-    BookmarkCompleter,
+    BufferCompleter,
+    ClosedWindowCompleter,
     FileCompleter,
+    HistoryCompleter,
+    SessionCompleter,
     TagCompleter,
     UserAgentCompleter,
 };
 use config_dir::ConfigDir;
+use control_server::{ControlQuery, RpcResult};
 use download_list_view::DownloadListView;
 use download_list_view::Msg::{
     ActiveDownloads,
@@ -129,25 +148,27 @@ use self::config::default_config;
 use self::dialog::handle_script_dialog;
 use self::file_chooser::handle_file_chooser;
 use self::Msg::*;
+use self::spawn::SpawnRequest;
 use self::user_agent::UserAgentManager;
-use settings::AppSettings;
+use session::{SessionTab, SessionWindow};
+use settings::{AppSettings, PrivateContext, TabBar};
 use settings::AppSettingsVariant::{
     self,
+    ConfigWatch,
     HintChars,
     HomePage,
+    OpenInTab,
     WebkitUserAgent,
 };
+use site_privacy::SitePrivacy;
+use site_settings::SiteSettings;
 use urls::canonicalize_url;
 use webview::WebView;
 use webview::Msg::{
     AddScripts,
     AddStylesheets,
-    AppError,
-    Close,
+    ApplySiteSettings,
     EndSearch,
-    EnterFullScreen,
-    LeaveFullScreen,
-    NewWindow,
     PageFinishSearch,
     PageOpen,
     PagePrint,
@@ -158,26 +179,32 @@ use webview::Msg::{
     PageZoomIn,
     PageZoomNormal,
     PageZoomOut,
-    PermissionRequest,
     SearchBackward,
     SetOpenInNewWindow,
     ShowInspector,
     WebViewSettingChanged,
-    ZoomChange,
 };
+use windows::{ClosedWindow, ClosedWindows, OpenedWindows};
 
 pub const APP_NAME: &'static str = env!("CARGO_PKG_NAME");
+pub const BUFFER_COMPLETER: &str = "buffer";
+const DEFAULT_HINT_CHARS: &str = "hjklasdfgyuiopqwertnmzxcvb";
 const INIT_SCROLL_TEXT: &str = "[top]";
+const SESSION_DELETE_COMPLETER: &str = "session-delete";
+const SESSION_LOAD_COMPLETER: &str = "session-load";
+const SESSION_SAVE_COMPLETER: &str = "session-save";
 const TAG_COMPLETER: &str = "__tag";
+pub const UNDO_CLOSE_COMPLETER: &str = "undo-close-select";
 pub const USER_AGENT_COMPLETER: &str = "select-user-agent";
 
-static MODES: Modes = &[
+pub static MODES: Modes = &[
     Mode { name: "follow", prefix: "f", show_count: false },
     Mode { name: "insert", prefix: "i", show_count: false },
 ];
 
 pub struct Model {
     bookmark_manager: BookmarkManager,
+    closed_windows: ClosedWindows,
     command_text: String,
     config_dir: ConfigDir,
     current_url: String,
@@ -187,33 +214,51 @@ pub struct Model {
     hint_chars: String,
     home_page: Option<String>,
     in_follow_mode: Rc<Cell<bool>>,
+    /// The saved window whose tabs are restored when the window is created.
+    init_window: Option<SessionWindow>,
     init_url: Option<String>,
     is_fullscreen: bool,
+    /// The control clients waiting for the current page to be loaded, with the id of their request.
+    load_waiters: Vec<(usize, Value)>,
     mode: String,
     open_in_new_window: bool,
+    open_in_tab: bool,
+    opened_windows: OpenedWindows,
     password_manager: PasswordManager,
     overridden_color: ForegroundColor,
+    pending_mark: Option<u8>,
     permission_manager: Option<PermissionManager>,
     popup_manager: Option<PopupManager>,
-    previous_opened_urls: BTreeSet<String>,
+    previous_windows: Vec<SessionWindow>,
+    privacy: Privacy,
     relm: Relm<App>,
     scroll_text: String,
     search_engines: HashMap<String, String>,
+    site_privacy: SitePrivacy,
+    site_settings: SiteSettings,
+    tab_bar: TabBar,
+    tabs: Vec<Component<WebView>>,
     title: String,
     user_agents: HashMap<String, String>,
     user_agent_manager: UserAgentManager,
     web_context: WebContext,
+    webview_settings: Vec<AppSettingsVariant>,
 }
 
 #[derive(Msg)]
 pub enum Msg {
+    AnswerControlQuery(usize, Value, ControlQuery),
     AppSetMode(String),
     AppSettingChanged(AppSettingsVariant),
     AskPermission(webkit2gtk::PermissionRequest),
+    ChangeTitle(String),
     ChangeUrl(String, String),
     Create(NavigationAction),
     Command(AppCommand),
     CommandText(String),
+    ConfigWatchChanged(bool),
+    ControlReply(usize, Value, RpcResult<Value>),
+    CreatePrivateWindow(String),
     CreateWindow(String, Privacy),
     DecideDownloadDestination(Download, String),
     DownloadDestination(DialogResult, Download, String),
@@ -223,21 +268,33 @@ pub enum Msg {
     HostfileDownloaded(String, Download),
     InsecureContent,
     KeyPress(EventKey),
-    LoadChanged(LoadEvent),
+    LoadChanged(webkit2gtk::WebView, LoadEvent),
     MessageRecv(InnerMessage),
     MouseTargetChanged(HitTestResult),
+    OpenNewPage(String),
     OverwriteDownload(Download, String, bool),
     PermissionResponse(webkit2gtk::PermissionRequest, Option<String>),
     PopupDecision(Option<String>, String),
-    Remove(String),
+    PrivateContextChanged(PrivateContext),
+    RedirectNavigation(String, Privacy),
+    /// Reload the configuration files in this window.
+    ReloadConfig,
+    /// Reload the configuration files in every window.
+    ReloadAllConfigs,
+    RemoteCommand(String),
+    Remove(ClosedWindow),
+    RestoreWindow(SessionWindow),
+    SessionStateChanged(usize, Vec<SessionTab>),
     ShowError(String),
     ShowZoom(i32),
+    SpawnProgram(SpawnRequest, String),
+    TabSwitched,
     TagEdit(Option<String>),
     TitleChanged,
     TryClose,
     UriChanged,
     WebProcessCrashed,
-    WebViewClose,
+    WebViewClose(webkit2gtk::WebView),
     WebViewFullscreen(bool),
 }
 
@@ -247,36 +304,67 @@ impl Widget for App {
     /// Show the URL.
     /// Set the window title.
     /// Go back to normal mode.
-    fn handle_load_changed(&mut self, load_event: LoadEvent) {
+    /// The window is only updated when the event comes from the current tab.
+    fn handle_load_changed(&mut self, webview: webkit2gtk::WebView, load_event: LoadEvent) {
+        let is_current_tab = webview == *self.webview();
+        if (load_event == Started || load_event == Redirected) && self.redirect_navigation(&webview) {
+            return;
+        }
         if load_event == Started {
-            self.model.overridden_color = ForegroundColor::None;
-            self.model.scroll_text = INIT_SCROLL_TEXT.to_string();
-            self.components.webview.emit(EndSearch);
-            self.components.webview.emit(AddStylesheets);
-            self.components.webview.emit(AddScripts);
-
-            // Check to mode to avoid going back to normal mode if the user is in command mode.
-            if self.model.mode == "insert" || self.model.mode == "follow" {
-                self.go_in_normal_mode();
+            if let Some(url) = webview.uri() {
+                self.show_private_site_info(&url);
+            }
+
+            if let Some(tab) = self.tab(&webview) {
+                tab.emit(ApplySiteSettings);
+                tab.emit(EndSearch);
+                tab.emit(AddStylesheets);
+                tab.emit(AddScripts);
+            }
+
+            if is_current_tab {
+                self.model.overridden_color = ForegroundColor::None;
+                self.model.scroll_text = INIT_SCROLL_TEXT.to_string();
+
+                // Check to mode to avoid going back to normal mode if the user is in command mode.
+                if self.model.mode == "insert" || self.model.mode == "follow" {
+                    self.go_in_normal_mode();
+                }
             }
         }
         else {
-            if let Some((_, cert_flags)) = self.widgets.webview.tls_info() {
-                // If there's a certificate error, show the URL in red.
-                if !cert_flags.is_empty() {
-                    self.model.overridden_color = ForegroundColor::Red;
+            if is_current_tab {
+                if let Some((_, cert_flags)) = webview.tls_info() {
+                    // If there's a certificate error, show the URL in red.
+                    if !cert_flags.is_empty() {
+                        self.model.overridden_color = ForegroundColor::Red;
+                    }
+                }
+            }
+
+            // The URL changes when the page is redirected.
+            if load_event == Redirected || load_event == Committed {
+                if let Some(tab) = self.tab(&webview) {
+                    tab.emit(ApplySiteSettings);
+                }
+            }
+
+            if load_event == Committed {
+                self.add_to_history(&webview);
+                self.save_session_state();
+            }
+            else if load_event == Finished {
+                self.update_history_title(&webview);
+                self.send_marks(&webview);
+                if is_current_tab {
+                    self.answer_load_waiters();
                 }
             }
         }
     }
 
     fn init_view(&mut self) {
-        handle_error!(self.model.bookmark_manager.create_tables());
-
-        match App::bookmark_path(&self.model.config_dir) {
-            Ok(bookmark_path) => handle_error!(self.model.bookmark_manager.connect(bookmark_path)),
-            Err(error) => self.error(&error.to_string()),
-        }
+        self.tab_open(None);
 
         handle_error!(self.clean_download_folder());
         self.init_permission_manager();
@@ -294,65 +382,90 @@ impl Widget for App {
         }
     }
 
-    fn model(relm: &Relm<Self>, (init_url, config_dir, web_context, previous_opened_urls): (Option<String>, ConfigDir, WebContext, BTreeSet<String>)) -> Model {
+    fn model(relm: &Relm<Self>, (init_url, init_window, config_dir, web_context, privacy, bookmark_manager,
+        previous_windows, closed_windows, opened_windows): (Option<String>, Option<SessionWindow>, ConfigDir,
+        WebContext, Privacy, BookmarkManager, Vec<SessionWindow>, ClosedWindows, OpenedWindows)) -> Model
+    {
         let permission_manager = create_permission_manager(&config_dir);
         let popup_manager = create_popup_manager(&config_dir);
+        let title = app_name_text(&config_dir);
+        let site_privacy = SitePrivacy::new(privacy.clone());
         Model {
-            bookmark_manager: BookmarkManager::new(),
+            bookmark_manager,
+            closed_windows,
             command_text: String::new(),
             config_dir,
             current_url: String::new(),
             default_search_engine: None,
             follow_mode: FollowMode::Click,
             has_active_downloads: false,
-            hint_chars: "hjklasdfgyuiopqwertnmzxcvb".to_string(),
+            hint_chars: DEFAULT_HINT_CHARS.to_string(),
             home_page: None,
             in_follow_mode: Rc::new(Cell::new(false)),
+            init_window,
             init_url,
             is_fullscreen: false,
+            load_waiters: vec![],
             mode: "normal".to_string(),
             open_in_new_window: false,
+            open_in_tab: false,
+            opened_windows,
             password_manager: PasswordManager::new(),
             overridden_color: ForegroundColor::None,
+            pending_mark: None,
             permission_manager,
             popup_manager,
-            previous_opened_urls,
+            previous_windows,
+            privacy,
             relm: relm.clone(),
             scroll_text: INIT_SCROLL_TEXT.to_string(),
             search_engines: HashMap::new(),
-            title: APP_NAME.to_string(),
+            site_privacy,
+            site_settings: SiteSettings::new(),
+            tab_bar: TabBar::default(),
+            tabs: vec![],
+            title,
             user_agents: HashMap::new(),
             user_agent_manager: UserAgentManager,
             web_context,
+            webview_settings: vec![],
         }
     }
 
     fn open_init_url(&mut self) {
+        if let Some(window) = self.model.init_window.take() {
+            self.restore_tabs(window);
+            return;
+        }
+
         if let Some(ref url) = self.model.init_url {
             // Open as a file if the path exist, otherwise open as a normal URL.
             let url = canonicalize_url(url);
-            self.components.webview.emit(PageOpen(url));
+            self.current_tab().emit(PageOpen(url));
         }
     }
 
     /// Set the title of the window as the progress and the web page title.
     fn set_title(&mut self) {
         let private = self.private_text();
-        let progress = (self.widgets.webview.estimated_load_progress() * 100.0) as i32;
+        let progress = (self.webview().estimated_load_progress() * 100.0) as i32;
         if progress == 100 {
             self.set_title_without_progress();
         }
         else {
             let title = self.get_title();
-            self.model.title = format!("[{}%]{} {}{}", progress, private, title, APP_NAME);
+            self.model.title = format!("[{}%]{} {}{}", progress, private, title, app_name_text(&self.model.config_dir));
         }
     }
 
     /// Set the title of the window as the web page title or url.
+    /// Also send the page title to be listed in the window switcher.
     fn set_title_without_progress(&mut self) {
         let private = self.private_text();
         let title = self.get_title();
-        self.model.title = format!("{}{}{}", private, title, APP_NAME);
+        self.model.title = format!("{}{}{}", private, title, app_name_text(&self.model.config_dir));
+        let page_title = self.webview().title().map(Into::into).unwrap_or_default();
+        self.model.relm.stream().emit(ChangeTitle(page_title));
     }
 
     /// Show the scroll percentage.
@@ -368,6 +481,7 @@ impl Widget for App {
 
     fn update(&mut self, event: Msg) {
         match event {
+            AnswerControlQuery(client, id, query) => self.answer_control_query(client, id, query),
             AppSetMode(mode) => {
                 self.adjust_in_follow_mode(&mode);
                 self.model.mode = mode
@@ -387,33 +501,40 @@ impl Widget for App {
             HostfileDownloaded(file, download) => handle_error!(self.process_hostfile(&file, download)),
             InsecureContent => self.insecure_content_detected(),
             KeyPress(event_key) => self.handle_key_press(event_key),
-            LoadChanged(load_event) => self.handle_load_changed(load_event),
+            LoadChanged(webview, load_event) => self.handle_load_changed(webview, load_event),
             MessageRecv(message) => self.message_recv(message),
             MouseTargetChanged(hit_test_result) => self.mouse_target_changed(hit_test_result),
             // To be listened by the user.
-            CreateWindow(_, _) => (),
+            ConfigWatchChanged(_) | ControlReply(_, _, _) | CreatePrivateWindow(_) | CreateWindow(_, _) |
+                PrivateContextChanged(_) | ReloadAllConfigs => (),
+            OpenNewPage(url) => self.open_in_new_page(&url),
             OverwriteDownload(download, download_destination, overwrite) =>
                 self.overwrite_download(download, download_destination, overwrite),
             PopupDecision(answer, url) => self.handle_answer(answer.as_ref().map(|str| str.as_str()), &url),
             PermissionResponse(request, choice) => self.handle_permission_response(&request, choice),
+            RedirectNavigation(url, privacy) => self.redirect(&url, privacy),
+            ReloadConfig => self.reload_config(),
+            RemoteCommand(command) => self.remote_command(&command),
             ShowError(error) => self.error(&error),
             ShowZoom(level) => self.show_zoom(level),
+            SpawnProgram(request, page) => self.spawn_program(request, &page),
+            TabSwitched => self.tab_switched(),
             TagEdit(tags) => self.set_tags(tags),
             TitleChanged => self.set_title(),
             TryClose => self.try_quit(),
             UriChanged => self.uri_changed(),
             WebProcessCrashed => self.web_process_crashed(),
-            WebViewClose => self.close_webview(),
+            WebViewClose(webview) => self.tab_close(&webview),
             WebViewFullscreen(fullscreen) => self.model.is_fullscreen = fullscreen,
 
             // To be listened by the user.
-            ChangeUrl(_, _) | Remove(_) => (),
+            ChangeTitle(_) | ChangeUrl(_, _) | Remove(_) | RestoreWindow(_) | SessionStateChanged(_, _) => (),
         }
     }
 
     /// Handle the URI changed event.
     fn uri_changed(&mut self) {
-        if let Some(url) = self.widgets.webview.uri() {
+        if let Some(url) = self.webview().uri() {
             let url = url.to_string();
             self.model.relm.stream().emit(ChangeUrl(self.model.current_url.clone(), url.clone()));
             self.model.current_url = url;
@@ -426,11 +547,21 @@ impl Widget for App {
             Some(self.model.config_dir.config_home()), default_config(&self.model.config_dir))
         {
             Completers: hash! {
+                BUFFER_COMPLETER => Box::new(BufferCompleter::new(self.model.opened_windows.clone())),
                 "file" => Box::new(FileCompleter::new()),
-                "open" => Box::new(BookmarkCompleter::new("open")),
-                "win-open" => Box::new(BookmarkCompleter::new("win-open")),
-                "private-win-open" => Box::new(BookmarkCompleter::new("private-win-open")),
-                TAG_COMPLETER => Box::new(TagCompleter::new()),
+                "open" => Box::new(HistoryCompleter::new("open", self.model.bookmark_manager.clone())),
+                "win-open" => Box::new(HistoryCompleter::new("win-open", self.model.bookmark_manager.clone())),
+                "private-win-open" =>
+                    Box::new(HistoryCompleter::new("private-win-open", self.model.bookmark_manager.clone())),
+                "tab-open" => Box::new(HistoryCompleter::new("tab-open", self.model.bookmark_manager.clone())),
+                SESSION_DELETE_COMPLETER =>
+                    Box::new(SessionCompleter::new(SESSION_DELETE_COMPLETER, self.model.config_dir.clone())),
+                SESSION_LOAD_COMPLETER =>
+                    Box::new(SessionCompleter::new(SESSION_LOAD_COMPLETER, self.model.config_dir.clone())),
+                SESSION_SAVE_COMPLETER =>
+                    Box::new(SessionCompleter::new(SESSION_SAVE_COMPLETER, self.model.config_dir.clone())),
+                TAG_COMPLETER => Box::new(TagCompleter::new(self.model.bookmark_manager.clone())),
+                UNDO_CLOSE_COMPLETER => Box::new(ClosedWindowCompleter::new(self.model.closed_windows.clone())),
                 USER_AGENT_COMPLETER => Box::new(UserAgentCompleter::new()),
             },
             //DarkTheme: true, // TODO: reenable when the webkit2gtk issue with dark theme is fixed.
@@ -442,24 +573,14 @@ impl Widget for App {
                     ActiveDownloads(active) => HasActiveDownloads(active),
                     DownloadListError(ref error) => ShowError(error.clone()),
                 },
-                #[name="webview"]
-                WebView((self.model.config_dir.clone(), self.model.web_context.clone())) {
-                    AppError(ref error) => ShowError(error.clone()),
-                    Close => WebViewClose,
-                    EnterFullScreen => WebViewFullscreen(true),
-                    LeaveFullScreen => WebViewFullscreen(false),
-                    NewWindow(ref url) => Command(WinOpen(url.clone())),
-                    PermissionRequest(ref request) => AskPermission(request.clone()),
-                    ZoomChange(level) => ShowZoom(level),
-                    create(_, action) => (Create(action.clone()), None),
-                    insecure_content_detected(_, _) => InsecureContent,
-                    load_changed(_, load_event) => LoadChanged(load_event),
-                    mouse_target_changed(_, hit_test_result, _) => MouseTargetChanged(hit_test_result.clone()),
-                    estimated_load_progress_notify(_) => TitleChanged,
-                    title_notify(_) => TitleChanged,
-                    uri_notify(_) => UriChanged,
-                    user_message_received(_, msg) => (message_recv(msg), true),
-                    web_process_crashed => (WebProcessCrashed, false),
+                // The tabs are added in init_view().
+                #[name="notebook"]
+                gtk::Notebook {
+                    scrollable: true,
+                    show_border: false,
+                    show_tabs: false,
+                    vexpand: true,
+                    page_notify(_) => TabSwitched,
                 },
             },
             #[name="scroll_label"]
@@ -470,6 +591,13 @@ impl Widget for App {
                 Color: self.model.overridden_color,
                 Text: self.model.current_url.clone(),
             },
+            StatusBarItem {
+                Text: container_text(&self.model.privacy),
+            },
+            StatusBarItem {
+                Color: ForegroundColor::Red,
+                Text: bookmark_database_text(&self.model.bookmark_manager),
+            },
             AppClose => TryClose,
             CompletionViewChange(ref completion) => CommandText(completion.clone()),
             CustomCommand(ref command) => Command(command.clone()),
@@ -482,6 +610,33 @@ impl Widget for App {
     }
 }
 
+/// Get the text shown in the status bar when the bookmarks and the history are not saved because the
+/// database could not be opened.
+fn bookmark_database_text(bookmark_manager: &BookmarkManager) -> String {
+    if bookmark_manager.is_temporary() {
+        "[bookmarks not saved]".to_string()
+    }
+    else {
+        String::new()
+    }
+}
+
+/// Get the text shown in the status bar for the container of the window, if any.
+fn container_text(privacy: &Privacy) -> String {
+    match *privacy {
+        Privacy::Container(ref name) => format!("[{}]", name),
+        Privacy::Normal | Privacy::Private => String::new(),
+    }
+}
+
+/// Get the application name shown in the title, with the name of the profile, if any.
+fn app_name_text(config_dir: &ConfigDir) -> String {
+    match config_dir.profile() {
+        Some(profile) => format!("{} [{}]", APP_NAME, profile),
+        None => APP_NAME.to_string(),
+    }
+}
+
 fn message_recv(msg: &UserMessage) -> Option<Msg> {
     match decode(&msg.parameters()) {
         Ok(msg) => Some(MessageRecv(msg)),
@@ -493,12 +648,6 @@ fn message_recv(msg: &UserMessage) -> Option<Msg> {
 }
 
 impl App {
-    fn add_mark(&mut self, mark: &str) {
-        let mark = mark_from_str(mark);
-        self.server_send(InnerMessage::Mark(mark));
-        self.components.mg.emit(Info(format!("Added mark {}", mark as char)));
-    }
-
     fn add_user_agent(&mut self, user_agent: &str) {
         let mut params = user_agent.splitn(2, ' ');
         if let (Some(name), Some(user_agent)) = (params.next(), params.next()) {
@@ -512,20 +661,20 @@ impl App {
     }
 
     fn close_webview(&self) {
-        self.model.relm.stream().emit(Remove(self.model.current_url.clone()));
+        self.model.relm.stream().emit(Remove(self.closed_window()));
 
         self.components.mg.emit(CloseWin);
     }
 
-    fn connect_dialog_events(&self) {
+    fn connect_dialog_events(&self, webview: &webkit2gtk::WebView) {
         let mg = self.streams.mg.clone();
-        connect!(self.model.relm, self.widgets.webview, connect_script_dialog(_, script_dialog),
+        connect!(self.model.relm, webview, connect_script_dialog(_, script_dialog),
             return handle_script_dialog(script_dialog, &mg));
 
         // TODO: add a #[stream(mg)] attribute in relm to support connecting an event to a
         // function while getting the stream (for use in view! {})?
         let mg = self.streams.mg.clone();
-        connect!(self.model.relm, self.widgets.webview, connect_run_file_chooser(_, file_chooser_request),
+        connect!(self.model.relm, webview, connect_run_file_chooser(_, file_chooser_request),
             return handle_file_chooser(&mg, file_chooser_request));
     }
 
@@ -536,25 +685,25 @@ impl App {
 
     /// Give the focus to the webview.
     fn focus_webview(&self) {
-        self.widgets.webview.grab_focus();
+        self.webview().grab_focus();
     }
 
     fn follow(&mut self) {
         self.model.follow_mode = FollowMode::Click;
         self.model.open_in_new_window = false;
-        self.components.webview.emit(SetOpenInNewWindow(false));
+        self.current_tab().emit(SetOpenInNewWindow(false));
         self.set_mode("follow");
         self.follow_link();
     }
 
     /// Get the size of the webview.
     fn get_webview_allocation(&self) -> Rectangle {
-        self.widgets.webview.allocation()
+        self.webview().allocation()
     }
 
     /// Get the title or the url if there are no title.
     fn get_title(&self) -> String {
-        let webview = &self.widgets.webview;
+        let webview = self.webview();
         let title = webview.title()
             .and_then(|title|
                 if title.is_empty() {
@@ -574,7 +723,7 @@ impl App {
     }
 
     fn get_webview_context(&self) -> Option<WebContext> {
-        let context = self.widgets.webview.context();
+        let context = self.webview().context();
         if context.is_none() {
             self.error("Cannot retrieve web view context");
         }
@@ -589,11 +738,6 @@ impl App {
         self.set_mode("normal");
     }
 
-    fn go_to_mark(&mut self, mark: &str) {
-        let mark = mark_from_str(mark);
-        self.server_send(InnerMessage::GoToMark(mark));
-    }
-
     /// Handle the command.
     fn handle_command(&mut self, command: &AppCommand) {
         match *command {
@@ -602,21 +746,27 @@ impl App {
             AddUserAgent(ref user_agent) => self.add_user_agent(user_agent),
             Back => self.history_back(),
             BackwardSearch(ref input) => {
-                self.components.webview.emit(SearchBackward(true));
-                self.components.webview.emit(PageSearch(input.clone()));
+                self.current_tab().emit(SearchBackward(true));
+                self.current_tab().emit(PageSearch(input.clone()));
             },
             Bookmark => self.bookmark(),
             BookmarkDel => self.delete_bookmark(),
             BookmarkEditTags => self.edit_bookmark_tags(),
+            BookmarkExport(ref path) => self.export_bookmarks(path),
+            BookmarkImport(ref path) => self.import_bookmarks(path),
+            Buffer(ref query) => self.buffer(query),
             ClearCache => self.clear_cache(),
             ClickNextPage => self.click_next_page(),
             ClickPrevPage => self.click_prev_page(),
+            ConfigReload => self.model.relm.stream().emit(ReloadAllConfigs),
+            ContainerDomain(ref args) => self.add_container_domain(args),
+            ContainerOpen(ref args) => self.container_open(args),
             CopyLinkUrl => self.copy_link_url(),
             CopyUrl => self.copy_current_url(),
             DeleteAllCookies => self.delete_all_cookies(),
             DeleteCookies(ref domain) => self.delete_cookies(domain),
             DeleteSelectedBookmark => self.delete_selected_bookmark(),
-            FinishSearch => self.components.webview.emit(PageFinishSearch),
+            FinishSearch => self.current_tab().emit(PageFinishSearch),
             FocusInput => self.focus_input(),
             Follow => self.follow(),
             Forward => self.history_forward(),
@@ -626,7 +776,7 @@ impl App {
             HideHints => self.hide_hints(),
             Hover => self.hover(),
             Insert => self.go_in_insert_mode(),
-            Inspector => self.components.webview.emit(ShowInspector),
+            Inspector => self.current_tab().emit(ShowInspector),
             KillWin => self.close_webview(),
             Mark(ref mark) => self.add_mark(mark),
             Normal => self.go_in_normal_mode(),
@@ -639,14 +789,16 @@ impl App {
             PasswordSubmit => handle_error!(self.submit_login_form()),
             PasteUrl => self.paste_url(),
             PreferredLanguage(ref language) => self.model.web_context.set_preferred_languages(&[&language]),
-            Print => self.components.webview.emit(PagePrint),
-            PrivateWinOpen(ref url) => self.open_in_new_window(url, Privacy::Private),
+            Print => self.current_tab().emit(PagePrint),
+            PrivateSite(ref args) => self.add_private_site(args),
+            PrivateWinOpen(ref url) => self.private_win_open(url),
+            ProfileOpen(ref name) => self.profile_open(name),
             Quit => self.try_quit(),
-            Reload => self.widgets.webview.reload(),
-            ReloadBypassCache => self.widgets.webview.reload_bypass_cache(),
+            Reload => self.webview().reload(),
+            ReloadBypassCache => self.webview().reload_bypass_cache(),
             RestoreUrls => self.restore_urls(),
             SaveLink => self.save_link(),
-            Screenshot(ref path) => self.components.webview.emit(PageScreenshot(path.clone())),
+            Screenshot(ref path) => self.current_tab().emit(PageScreenshot(path.clone())),
             ScrollDown => self.scroll_down_page(),
             ScrollDownHalf => self.scroll_down_half_page(),
             ScrollDownLine => self.scroll_down_line(),
@@ -658,14 +810,26 @@ impl App {
             ScrollUpHalf => self.scroll_up_half_page(),
             ScrollUpLine => self.scroll_up_line(),
             Search(ref input) => {
-                self.components.webview.emit(SearchBackward(false));
-                self.components.webview.emit(PageSearch(input.clone()));
+                self.current_tab().emit(SearchBackward(false));
+                self.current_tab().emit(PageSearch(input.clone()));
             },
             SearchEngine(ref args) => self.add_search_engine(args),
-            SearchNext => self.components.webview.emit(PageSearchNext),
-            SearchPrevious => self.components.webview.emit(PageSearchPrevious),
+            SearchNext => self.current_tab().emit(PageSearchNext),
+            SearchPrevious => self.current_tab().emit(PageSearchPrevious),
             SelectUserAgent(ref name) => self.select_user_agent(name),
-            Stop => self.widgets.webview.stop_loading(),
+            SessionDelete(ref name) => self.session_delete(name),
+            SessionLoad(ref name) => self.session_load(name),
+            SessionSave(ref name) => self.session_save(name),
+            SetFor(ref args) => self.set_for(args),
+            Spawn(ref args) => self.spawn(args),
+            Stop => self.webview().stop_loading(),
+            TabClose => self.webview().try_close(),
+            TabMove(ref position) => self.tab_move(position),
+            TabNext(count) => self.tab_next(count),
+            TabOpen(ref url) => self.tab_open(Some(url)),
+            TabPrev(count) => self.tab_prev(count),
+            UndoClose(count) => self.undo_close(count),
+            UndoCloseSelect(ref url) => self.undo_close_select(url),
             UrlIncrement => self.url_increment(),
             UrlDecrement => self.url_decrement(),
             WinFollow => self.win_follow(),
@@ -694,7 +858,7 @@ impl App {
                         return;
                     }
                 }
-                self.open_in_new_window(&url, Privacy::Normal);
+                self.open_in_new_page(&url);
             }
         }
     }
@@ -714,7 +878,7 @@ impl App {
     }
 
     fn handle_permission_request(&mut self, request: &webkit2gtk::PermissionRequest) {
-        if let Some(url) = self.widgets.webview.uri() {
+        if let Some(url) = self.webview().uri() {
             if let Some(ref mut permission_manager) = self.model.permission_manager {
                 if permission_manager.is_blacklisted(&url, request) {
                     request.deny();
@@ -752,7 +916,7 @@ impl App {
     }
 
     fn handle_permission_response(&mut self, request: &webkit2gtk::PermissionRequest, choice: Option<String>) {
-        if let Some(url) = self.widgets.webview.uri() {
+        if let Some(url) = self.webview().uri() {
             match choice.as_ref().map(String::as_str) {
                 Some("y") | Some("a") => request.allow(),
                 _ => request.deny(),
@@ -781,12 +945,12 @@ impl App {
     }
 
     fn history_back(&mut self) {
-        self.widgets.webview.go_back();
+        self.webview().go_back();
         self.server_send(InnerMessage::ResetScrollElement());
     }
 
     fn history_forward(&mut self) {
-        self.widgets.webview.go_forward();
+        self.webview().go_forward();
         self.server_send(InnerMessage::ResetScrollElement());
     }
 
@@ -800,6 +964,35 @@ impl App {
         Inhibit(in_follow_mode.get())
     }
 
+    /// Run a script in the current page and send its result, as a string, in the message created by
+    /// `to_msg`.
+    pub fn run_script<F>(&self, script: &str, to_msg: F)
+    where F: FnOnce(String) -> Msg + 'static,
+    {
+        // The JavaScript callback must be Send, so the result is sent back to the main loop
+        // through a channel.
+        let (sender, receiver) = MainContext::channel(glib::PRIORITY_DEFAULT);
+        let stream = self.model.relm.stream().clone();
+        let mut to_msg = Some(to_msg);
+        receiver.attach(None, move |result: String| {
+            if let Some(to_msg) = to_msg.take() {
+                stream.emit(to_msg(result));
+            }
+            Continue(false)
+        });
+        self.webview().run_javascript(script, None::<&Cancellable>, move |result| {
+            let result = result.ok()
+                .and_then(|result| {
+                    let context = result.global_context()?;
+                    result.value()?.to_string(&context)
+                })
+                .unwrap_or_default();
+            if let Err(error) = sender.send(result) {
+                error!("Cannot send the result of the script: {}", error);
+            }
+        });
+    }
+
     /// Show an info.
     pub fn info(&self, info: String) {
         self.components.mg.emit(Info(info));
@@ -838,7 +1031,7 @@ impl App {
     }
 
     fn private_text(&self) -> &'static str {
-        if self.widgets.webview.is_ephemeral() {
+        if self.model.privacy == Privacy::Private {
             "[PV] "
         }
         else {
@@ -848,15 +1041,21 @@ impl App {
 
     /// Close the web view and quit the application if there's no download or the user chose to
     /// cancel them.
+    /// When there are many tabs, the window is closed directly.
     fn quit(&self, can_quit: bool) {
         if can_quit {
-            self.widgets.webview.try_close();
+            if self.model.tabs.len() > 1 {
+                self.close_webview();
+            }
+            else {
+                self.webview().try_close();
+            }
         }
     }
 
     fn restore_urls(&mut self) {
-        for url in &self.model.previous_opened_urls {
-            self.open_in_new_window(url, Privacy::Normal);
+        for window in &self.model.previous_windows {
+            self.model.relm.stream().emit(RestoreWindow(window.clone()));
         }
     }
 
@@ -874,14 +1073,29 @@ impl App {
 
     fn setting_changed(&mut self, setting: AppSettingsVariant) {
         match setting {
+            ConfigWatch(watch) => self.model.relm.stream().emit(ConfigWatchChanged(watch)),
             HintChars(chars) => self.model.hint_chars = chars,
             HomePage(url) => {
                 if  self.model.init_url.is_none() {
-                    self.components.webview.emit(PageOpen(url.clone()));
+                    self.current_tab().emit(PageOpen(url.clone()));
                 }
                 self.model.home_page = Some(url);
             },
-            _ => self.components.webview.emit(WebViewSettingChanged(setting)),
+            OpenInTab(open_in_tab) => self.model.open_in_tab = open_in_tab,
+            AppSettingsVariant::PrivateContext(private_context) =>
+                self.model.relm.stream().emit(PrivateContextChanged(private_context)),
+            AppSettingsVariant::TabBar(tab_bar) => {
+                self.model.tab_bar = tab_bar;
+                self.update_tab_bar();
+            },
+            _ => {
+                // Remember the setting to apply it to the tabs created later.
+                self.model.webview_settings.retain(|previous| discriminant(previous) != discriminant(&setting));
+                self.model.webview_settings.push(setting.clone());
+                for tab in &self.model.tabs {
+                    tab.emit(WebViewSettingChanged(setting.clone()));
+                }
+            },
         }
     }
 
@@ -911,30 +1125,23 @@ impl App {
     fn win_follow(&mut self) {
         self.model.follow_mode = FollowMode::Click;
         self.model.open_in_new_window = true;
-        self.components.webview.emit(SetOpenInNewWindow(true));
+        self.current_tab().emit(SetOpenInNewWindow(true));
         self.set_mode("follow");
         self.follow_link();
     }
 
     /// Zoom in.
     fn zoom_in(&self) {
-        self.components.webview.emit(PageZoomIn);
+        self.current_tab().emit(PageZoomIn);
     }
 
     /// Zoom back to 100%.
     fn zoom_normal(&self) {
-        self.components.webview.emit(PageZoomNormal);
+        self.current_tab().emit(PageZoomNormal);
     }
 
     /// Zoom out.
     fn zoom_out(&self) {
-        self.components.webview.emit(PageZoomOut);
+        self.current_tab().emit(PageZoomOut);
     }
 }
-
-fn mark_from_str(mark: &str) -> u8 {
-    mark.as_bytes().get(0)
-            .cloned()
-            .unwrap_or(LAST_MARK)
-
-}
@@ -0,0 +1,45 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Websites that are always opened in a private window.
+
+use app::App;
+use message_server::Privacy;
+
+impl App {
+    /// Add an URL pattern to the list of websites always opened in a private window.
+    pub fn add_private_site(&mut self, args: &str) {
+        let args: Vec<_> = args.split_whitespace().collect();
+        if args.len() == 1 {
+            self.model.site_privacy.add_private_site(args[0]);
+        }
+        else {
+            self.error(&format!("private-site: expecting 1 argument, got {} arguments", args.len()));
+        }
+    }
+
+    /// Tell the user why the page is shown in a private window.
+    pub fn show_private_site_info(&self, url: &str) {
+        if self.model.privacy == Privacy::Private && self.model.site_privacy.is_private_site(url) {
+            self.info(format!("Private window: {} is in the private site list", url));
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Opening the windows of other profiles.
+
+use std::env::current_exe;
+use std::process::Command;
+
+use app::App;
+use config_dir::is_valid_name;
+use errors::{Error, Result};
+
+impl App {
+    /// Open a window with the specified profile.
+    /// Every profile runs in its own process, since the cookies and the web processes cannot be
+    /// shared between profiles.
+    pub fn profile_open(&self, name: &str) {
+        match self.spawn_profile(name) {
+            Ok(()) => self.info(format!("Opening profile {}", name)),
+            Err(error) => self.error(&format!("Cannot open profile {}: {}", name, error)),
+        }
+    }
+
+    fn spawn_profile(&self, name: &str) -> Result<()> {
+        if !is_valid_name(name) {
+            return Err(Error::new("a profile name can only contain letters, digits, - and _"));
+        }
+        let mut command = Command::new(current_exe()?);
+        if let Some(config_dir) = self.model.config_dir.custom_dir() {
+            command.arg("--config").arg(config_dir);
+        }
+        command.arg("--profile").arg(name);
+        command.spawn()?;
+        Ok(())
+    }
+}
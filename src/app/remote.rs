@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Commands sent by other nuon processes with the --remote option.
+
+use mg_settings::EnumFromStr;
+
+use app::App;
+use commands::AppCommand;
+use errors::{Error, Result};
+
+impl App {
+    /// Run a command sent by another nuon process.
+    pub fn remote_command(&mut self, command: &str) {
+        match parse_command(command) {
+            Ok(command) => self.handle_command(&command),
+            Err(error) => self.error(&format!("Remote command {}", error)),
+        }
+    }
+}
+
+/// Parse a command like the ones typed in the command entry, for instance `open example.com`.
+pub fn parse_command(command: &str) -> Result<AppCommand> {
+    let command = command.trim();
+    let (name, argument) =
+        match command.find(char::is_whitespace) {
+            Some(index) => (&command[..index], command[index..].trim()),
+            None => (command, ""),
+        };
+    AppCommand::create(name, argument, None)
+        .map_err(|error| Error::from_string(format!("{}: {}", name, error)))
+}
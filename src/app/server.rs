@@ -22,7 +22,7 @@
 use gio::Cancellable;
 use glib::ToVariant;
 use nuon_common::protocol::encode;
-use webkit2gtk::{WebViewExt, UserMessage};
+use webkit2gtk::{self, WebViewExt, UserMessage};
 
 use nuon_common::InnerMessage;
 use nuon_common::InnerMessage::*;
@@ -85,6 +85,7 @@ impl App {
             ClickHintElement() => self.click_hint_element(),
             Credentials(ref username, ref password) => handle_error!(self.save_username_password(&username, &password)),
             EnterInsertMode() => self.go_in_insert_mode(),
+            MarkPosition(mark, position) => self.save_mark(mark, position),
             ScrollPercentage(percentage) => self.show_scroll(percentage),
             _ =>
                 // TODO: show the warning in the UI?
@@ -156,7 +157,14 @@ impl App {
         self.server_send(SelectFile(file));
     }
 
+    /// Send a message to the web process of the current tab.
     pub fn server_send(&mut self, message: InnerMessage) {
+        let webview = self.webview().clone();
+        self.server_send_to(&webview, message);
+    }
+
+    /// Send a message to the web process of the specified web view.
+    pub fn server_send_to(&mut self, webview: &webkit2gtk::WebView, message: InnerMessage) {
         let bytes =
             match encode(message) {
                 Ok(message) => message,
@@ -166,6 +174,6 @@ impl App {
                 },
             };
         let message = UserMessage::new("", Some(&bytes.to_variant()));
-        self.widgets.webview.send_message_to_page(&message, None::<&Cancellable>, |_| {});
+        webview.send_message_to_page(&message, None::<&Cancellable>, |_| {});
     }
 }
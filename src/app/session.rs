@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Saving and loading sessions.
+
+use glib::{self, Bytes};
+use gtk::traits::NotebookExt;
+use webkit2gtk::{self, BackForwardListExt, WebViewExt, WebViewSessionState};
+
+use app::App;
+use app::Msg::{RestoreWindow, SessionStateChanged};
+use session::{self, Session, SessionTab, SessionWindow};
+use webview::Msg::PageOpen;
+
+impl App {
+    /// Delete the session with the specified name.
+    pub fn session_delete(&self, name: &str) {
+        match session::delete(&self.model.config_dir, name) {
+            Ok(()) => self.info(format!("Session {} deleted", name)),
+            Err(error) => self.error(&error.to_string()),
+        }
+    }
+
+    /// Open the windows of the session with the specified name.
+    pub fn session_load(&self, name: &str) {
+        match session::load(&self.model.config_dir, name) {
+            Ok(session) => {
+                for window in session.windows {
+                    self.model.relm.stream().emit(RestoreWindow(window));
+                }
+            },
+            Err(error) => self.error(&format!("Cannot load session {}: {}", name, error)),
+        }
+    }
+
+    /// Save every opened window in the session with the specified name.
+    pub fn session_save(&self, name: &str) {
+        let session = Session::new(self.model.opened_windows.list());
+        match session::save(&self.model.config_dir, name, &session) {
+            Ok(()) => self.info(format!("Session {} saved", name)),
+            Err(error) => self.error(&format!("Cannot save session {}: {}", name, error)),
+        }
+    }
+
+    /// Restore the back/forward history of the current tab and load its current page.
+    /// Return false if the history cannot be restored.
+    fn restore_session_state(&self, state: &str) -> bool {
+        let data = glib::base64_decode(state);
+        if data.is_empty() {
+            warn!("Cannot decode the session state");
+            return false;
+        }
+        let webview = self.webview();
+        webview.restore_session_state(&WebViewSessionState::new(&Bytes::from_owned(data)));
+        match webview.back_forward_list().and_then(|list| list.current_item()) {
+            Some(item) => {
+                webview.go_to_back_forward_list_item(&item);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Open the tabs of a saved window, restoring their back/forward history when possible.
+    pub fn restore_tabs(&mut self, window: SessionWindow) {
+        for (index, tab) in window.tabs.into_iter().enumerate() {
+            if index > 0 && !self.tab_new() {
+                return;
+            }
+            let restored = tab.state.map_or(false, |state| self.restore_session_state(&state));
+            if !restored {
+                self.current_tab().emit(PageOpen(tab.url));
+            }
+        }
+        self.widgets.notebook.set_current_page(Some(window.current_tab as u32));
+    }
+
+    /// Send the tabs of this window, with their back/forward history, to be saved for crash recovery.
+    pub fn save_session_state(&self) {
+        let current_tab = self.widgets.notebook.current_page().unwrap_or(0) as usize;
+        self.model.relm.stream().emit(SessionStateChanged(current_tab, self.session_tabs()));
+    }
+
+    /// Get the URL and back/forward history of every tab of this window.
+    /// The history is not saved in private windows.
+    pub fn session_tabs(&self) -> Vec<SessionTab> {
+        self.model.tabs.iter()
+            .map(|tab| {
+                let webview = tab.widget();
+                SessionTab {
+                    state: session_state(webview),
+                    url: webview.uri().map(Into::into).unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Get the serialized back/forward history of a web view, unless it is private.
+fn session_state(webview: &webkit2gtk::WebView) -> Option<String> {
+    if webview.is_ephemeral() {
+        return None;
+    }
+
+    webview.session_state()
+        .and_then(|state| state.serialize())
+        .map(|state| glib::base64_encode(&state).to_string())
+}
@@ -0,0 +1,42 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Settings overridden for some websites.
+
+use app::App;
+
+impl App {
+    /// Override a setting for a website, like `set-for example.com webkit-enable-javascript = false`.
+    pub fn set_for(&mut self, args: &str) {
+        let mut args = args.trim().splitn(2, char::is_whitespace);
+        let pattern = args.next().unwrap_or("");
+        let mut assignment = args.next().unwrap_or("").splitn(2, '=');
+        let name = assignment.next().unwrap_or("").trim();
+        match assignment.next() {
+            Some(value) if !pattern.is_empty() && !name.is_empty() => {
+                if let Err(error) = self.model.site_settings.add(pattern, name, value) {
+                    self.error(&format!("set-for: {}", error));
+                }
+            },
+            _ => self.error("set-for: expecting a pattern and an assignment, like example.com setting = value"),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Spawning of external programs with the context of the page.
+//!
+//! The arguments are split like in a shell and can contain `{url}`, `{title}` and `{selection}`.
+//! A userscript also gets the page in environment variables and can send nuon commands, one per
+//! line, in the FIFO named by `NUON_FIFO`.
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process::Command;
+
+use glib::{Continue, IOCondition, Pid, child_watch_add_local, shell_parse_argv, unix_fd_add_local};
+use libc;
+use serde_json::{self, Value};
+use tempfile::{Builder as TempFileBuilder, TempDir, TempPath};
+use webkit2gtk::WebViewExt;
+
+use app::App;
+use app::Msg::{RemoteCommand, SpawnProgram};
+use errors::{Error, Result};
+
+/// The script getting the selection and, for userscripts, the HTML of the page.
+const PAGE_SCRIPT: &str = "JSON.stringify({
+    selection: window.getSelection().toString(),
+    html: USERSCRIPT ? document.documentElement.outerHTML : null,
+})";
+
+/// The name of the FIFO in the temporary directory of a userscript.
+const FIFO_NAME: &str = "fifo";
+
+/// A program to spawn once the context of the page is known.
+#[derive(Clone)]
+pub struct SpawnRequest {
+    args: Vec<String>,
+    program: String,
+    userscript: bool,
+}
+
+/// The context of the page given to the spawned programs.
+struct PageContext {
+    html: String,
+    selection: String,
+    title: String,
+    url: String,
+}
+
+impl App {
+    /// Run a program with the context of the current page.
+    pub fn spawn(&self, args: &str) {
+        if args.trim().is_empty() {
+            self.error("spawn: expecting a program");
+            return;
+        }
+        let mut args: Vec<_> =
+            match shell_parse_argv(args) {
+                Ok(args) => args.into_iter()
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect(),
+                Err(error) => {
+                    self.error(&format!("spawn: {}", error));
+                    return;
+                },
+            };
+        let userscript = args.first().map(|arg| arg == "--userscript").unwrap_or(false);
+        if userscript {
+            args.remove(0);
+        }
+        if args.is_empty() {
+            self.error("spawn: expecting a program");
+            return;
+        }
+        let program = args.remove(0);
+        let script = PAGE_SCRIPT.replace("USERSCRIPT", if userscript { "true" } else { "false" });
+        let request = SpawnRequest {
+            args,
+            program,
+            userscript,
+        };
+        self.run_script(&script, move |result| SpawnProgram(request, result));
+    }
+
+    /// Run the program, now that the selection and the HTML of the page are known.
+    pub fn spawn_program(&self, request: SpawnRequest, page: &str) {
+        let page: Option<Value> = serde_json::from_str(page).ok();
+        let page_string = |name| page.as_ref()
+            .and_then(|page| page.get(name))
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string();
+        let context = PageContext {
+            html: page_string("html"),
+            selection: page_string("selection"),
+            title: self.webview().title().map(|title| title.to_string()).unwrap_or_default(),
+            url: self.webview().uri().map(|url| url.to_string()).unwrap_or_default(),
+        };
+        let program = request.program.clone();
+        if let Err(error) = self.run_program(request, &context) {
+            self.error(&format!("spawn: cannot run {}: {}", program, error));
+        }
+    }
+
+    fn run_program(&self, request: SpawnRequest, context: &PageContext) -> Result<()> {
+        let args: Vec<_> = request.args.iter()
+            .map(|arg| substitute(arg, context))
+            .collect();
+        let mut command = Command::new(&request.program);
+        command.args(&args);
+        let userscript =
+            if request.userscript {
+                let userscript = Userscript::new(&context.html)?;
+                command.env("NUON_FIFO", userscript.fifo_path())
+                    .env("NUON_HTML", &userscript.html)
+                    .env("NUON_MODE", &self.model.mode)
+                    .env("NUON_SELECTION", &context.selection)
+                    .env("NUON_TITLE", &context.title)
+                    .env("NUON_URL", &context.url);
+                Some(userscript)
+            }
+            else {
+                None
+            };
+        let child = command.spawn()?;
+
+        let mut fifo_writer = None;
+        if let Some(userscript) = userscript {
+            fifo_writer = Some(userscript.listen(self)?);
+        }
+        // Reap the process and, once it exits, stop keeping the FIFO opened so that the reader
+        // reaches the end of the commands.
+        child_watch_add_local(Pid(child.id() as i32), move |_, _| {
+            fifo_writer.take();
+        });
+        Ok(())
+    }
+}
+
+/// Replace the placeholders in an argument with the context of the page.
+fn substitute(arg: &str, context: &PageContext) -> String {
+    arg.replace("{url}", &context.url)
+        .replace("{title}", &context.title)
+        .replace("{selection}", &context.selection)
+}
+
+/// The temporary files of a userscript.
+struct Userscript {
+    directory: TempDir,
+    html: TempPath,
+}
+
+impl Userscript {
+    fn new(html: &str) -> Result<Self> {
+        let directory = TempFileBuilder::new()
+            .prefix("nuon-userscript")
+            .tempdir()?;
+        let fifo = directory.path().join(FIFO_NAME);
+        let path = CString::new(fifo.as_os_str().as_bytes())
+            .map_err(|_| Error::new("invalid FIFO path"))?;
+        if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut html_file = TempFileBuilder::new()
+            .prefix("nuon-page")
+            .suffix(".html")
+            .tempfile()?;
+        html_file.write_all(html.as_bytes())?;
+        Ok(Userscript {
+            directory,
+            html: html_file.into_temp_path(),
+        })
+    }
+
+    fn fifo_path(&self) -> PathBuf {
+        self.directory.path().join(FIFO_NAME)
+    }
+
+    /// Execute the commands written by the userscript in the FIFO.
+    /// Returns the write end of the FIFO, which must be kept opened while the userscript runs.
+    fn listen(self, app: &App) -> Result<File> {
+        let fifo = self.fifo_path();
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&fifo)?;
+        let writer = OpenOptions::new()
+            .write(true)
+            .open(&fifo)?;
+        let stream = app.model.relm.stream().clone();
+        let mut buffer = vec![];
+        unix_fd_add_local(reader.as_raw_fd(), IOCondition::IN | IOCondition::HUP, move |_, _| {
+            // The temporary files are deleted with the closure, when the FIFO is closed.
+            let _files = &self;
+            let mut bytes = [0; 4096];
+            let end =
+                match reader.read(&mut bytes) {
+                    Ok(0) => true,
+                    Ok(size) => {
+                        buffer.extend_from_slice(&bytes[..size]);
+                        false
+                    },
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => false,
+                    Err(error) => {
+                        warn!("Cannot read the userscript FIFO: {}", error);
+                        true
+                    },
+                };
+            if end {
+                // Run the last command even if it does not end with a newline.
+                buffer.push(b'\n');
+            }
+            while let Some(index) = buffer.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<_> = buffer.drain(..=index).collect();
+                let command = String::from_utf8_lossy(&line).trim().to_string();
+                if !command.is_empty() {
+                    stream.emit(RemoteCommand(command));
+                }
+            }
+            Continue(!end)
+        });
+        Ok(writer)
+    }
+}
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Management of the tabs of a window.
+
+use glib::Cast;
+use gtk::{self, Notebook};
+use gtk::prelude::NotebookExtManual;
+use gtk::traits::{NotebookExt, WidgetExt};
+use relm::{Component, init};
+use webkit2gtk::{self, WebViewExt};
+
+use app::{App, message_recv};
+use app::Msg::{
+    AskPermission,
+    Create,
+    InsecureContent,
+    LoadChanged,
+    MouseTargetChanged,
+    OpenNewPage,
+    RedirectNavigation,
+    ShowError,
+    ShowZoom,
+    TitleChanged,
+    UriChanged,
+    WebProcessCrashed,
+    WebViewClose,
+    WebViewFullscreen,
+};
+use settings::TabBar;
+use webview::WebView;
+use webview::Msg::{
+    AppError,
+    Close,
+    EnterFullScreen,
+    LeaveFullScreen,
+    NewWindow,
+    PermissionRequest,
+    Redirect,
+    WebViewSettingChanged,
+    ZoomChange,
+};
+
+impl App {
+    /// Connect the events of the web view of a tab.
+    /// Only the events changing what is shown in the window are ignored when the tab is not the current one.
+    fn connect_tab(&self, tab: &Component<WebView>) {
+        let webview = tab.widget().clone();
+        connect!(tab@AppError(ref error), self.model.relm, ShowError(error.clone()));
+        connect!(tab@Close, self.model.relm, WebViewClose(webview.clone()));
+        connect!(tab@EnterFullScreen, self.model.relm, WebViewFullscreen(true));
+        connect!(tab@LeaveFullScreen, self.model.relm, WebViewFullscreen(false));
+        connect!(tab@NewWindow(ref url), self.model.relm, OpenNewPage(url.clone()));
+        connect!(tab@PermissionRequest(ref request), self.model.relm, AskPermission(request.clone()));
+        connect!(tab@Redirect(ref url, ref privacy), self.model.relm, RedirectNavigation(url.clone(), privacy.clone()));
+        connect!(tab@ZoomChange(ref level), self.model.relm, ShowZoom(*level));
+
+        connect!(self.model.relm, webview, connect_create(_, action), return (Create(action.clone()), None));
+        connect!(self.model.relm, webview, connect_estimated_load_progress_notify(_), TitleChanged);
+        let notebook = self.widgets.notebook.clone();
+        connect!(self.model.relm, webview, connect_insecure_content_detected(webview, _),
+            if is_current_tab(&notebook, webview) { Some(InsecureContent) } else { None });
+        connect!(self.model.relm, webview, connect_load_changed(webview, load_event),
+            LoadChanged(webview.clone(), load_event));
+        connect!(self.model.relm, webview, connect_mouse_target_changed(_, hit_test_result, _),
+            MouseTargetChanged(hit_test_result.clone()));
+        connect!(self.model.relm, webview, connect_title_notify(_), TitleChanged);
+        connect!(self.model.relm, webview, connect_uri_notify(_), UriChanged);
+        let notebook = self.widgets.notebook.clone();
+        connect!(self.model.relm, webview, connect_user_message_received(webview, msg),
+            return (if is_current_tab(&notebook, webview) { message_recv(msg) } else { None }, true));
+        connect!(self.model.relm, webview, connect_web_process_crashed(_), return (WebProcessCrashed, false));
+        self.connect_dialog_events(&webview);
+
+        let notebook = self.widgets.notebook.clone();
+        webview.connect_title_notify(move |webview| {
+            notebook.set_tab_label_text(webview, &tab_title(webview));
+        });
+    }
+
+    /// Get the component of the current tab.
+    /// While the notebook and the tab list are being updated, the last tab is used.
+    pub fn current_tab(&self) -> &Component<WebView> {
+        let index = self.widgets.notebook.current_page().unwrap_or(0) as usize;
+        self.model.tabs.get(index)
+            .or_else(|| self.model.tabs.last())
+            .expect("a window always has a tab")
+    }
+
+    /// Get the component of the tab showing the specified web view.
+    pub fn tab(&self, webview: &webkit2gtk::WebView) -> Option<&Component<WebView>> {
+        self.widgets.notebook.page_num(webview)
+            .and_then(|index| self.model.tabs.get(index as usize))
+    }
+
+    /// Close the tab showing the specified web view.
+    /// The window is closed when it is the last tab.
+    pub fn tab_close(&mut self, webview: &webkit2gtk::WebView) {
+        if self.model.tabs.len() <= 1 {
+            self.close_webview();
+            return;
+        }
+
+        if let Some(index) = self.widgets.notebook.page_num(webview) {
+            self.widgets.notebook.remove_page(Some(index));
+            self.model.tabs.remove(index as usize);
+            self.update_tab_bar();
+            self.save_session_state();
+        }
+    }
+
+    /// Move the current tab to the specified position.
+    /// The position is either relative to the current one, like +1 or -2, or absolute, starting at 1.
+    pub fn tab_move(&mut self, position: &str) {
+        let current = self.widgets.notebook.current_page().unwrap_or(0) as usize;
+        match tab_position(position, current, self.model.tabs.len()) {
+            Some(position) => {
+                self.widgets.notebook.reorder_child(self.webview(), Some(position as u32));
+                let tab = self.model.tabs.remove(current);
+                self.model.tabs.insert(position, tab);
+                self.save_session_state();
+            },
+            None => self.error(&format!("Invalid tab position: {}", position)),
+        }
+    }
+
+    /// Switch to the `count` next tab, going back to the first one after the last one.
+    pub fn tab_next(&self, count: Option<u32>) {
+        let tab_count = self.model.tabs.len();
+        let current = self.widgets.notebook.current_page().unwrap_or(0) as usize;
+        let index = (current + count.unwrap_or(1) as usize) % tab_count;
+        self.widgets.notebook.set_current_page(Some(index as u32));
+    }
+
+    /// Create an empty tab next to the current one and switch to it.
+    /// Return false if the tab cannot be created.
+    pub fn tab_new(&mut self) -> bool {
+        let tab =
+            match init::<WebView>((self.model.config_dir.clone(), self.model.web_context.clone(),
+                self.model.site_privacy.clone(), self.model.site_settings.clone()))
+            {
+                Ok(tab) => tab,
+                Err(()) => {
+                    self.error("Cannot create a new tab");
+                    return false;
+                },
+            };
+        self.connect_tab(&tab);
+        for setting in &self.model.webview_settings {
+            tab.emit(WebViewSettingChanged(setting.clone()));
+        }
+        tab.widget().show();
+
+        let position = self.widgets.notebook.current_page().map(|index| index + 1);
+        let index = self.widgets.notebook.insert_page(tab.widget(), None::<&gtk::Widget>, position);
+        self.model.tabs.insert(index as usize, tab);
+        self.update_tab_bar();
+        self.widgets.notebook.set_current_page(Some(index));
+        true
+    }
+
+    /// Create a new tab next to the current one and switch to it.
+    /// The URL is opened in this tab if it is specified, otherwise the home page is opened, if any.
+    pub fn tab_open(&mut self, url: Option<&str>) {
+        if !self.tab_new() {
+            return;
+        }
+
+        let url = url.filter(|url| !url.is_empty())
+            .map(ToString::to_string)
+            .or_else(|| self.model.home_page.clone());
+        if let Some(url) = url {
+            self.open(&url);
+        }
+    }
+
+    /// Switch to the `count` previous tab, going to the last one before the first one.
+    pub fn tab_prev(&self, count: Option<u32>) {
+        let tab_count = self.model.tabs.len();
+        let current = self.widgets.notebook.current_page().unwrap_or(0) as usize;
+        let count = count.unwrap_or(1) as usize % tab_count;
+        let index = (current + tab_count - count) % tab_count;
+        self.widgets.notebook.set_current_page(Some(index as u32));
+    }
+
+    /// Update the window after the current tab changed.
+    pub fn tab_switched(&mut self) {
+        self.set_title();
+        self.uri_changed();
+        self.save_session_state();
+        self.focus_webview();
+    }
+
+    /// Show or hide the tab bar according to the tab-bar setting.
+    pub fn update_tab_bar(&self) {
+        let visible =
+            match self.model.tab_bar {
+                TabBar::Always => true,
+                TabBar::Multiple => self.model.tabs.len() > 1,
+                TabBar::Never => false,
+            };
+        self.widgets.notebook.set_show_tabs(visible);
+    }
+
+    /// Get the web view of the current tab.
+    pub fn webview(&self) -> &webkit2gtk::WebView {
+        self.current_tab().widget()
+    }
+}
+
+/// Get the web view of the current tab of the notebook.
+pub fn current_webview(notebook: &Notebook) -> Option<webkit2gtk::WebView> {
+    notebook.nth_page(notebook.current_page())
+        .and_then(|page| page.downcast().ok())
+}
+
+/// Check whether the web view is in the current tab of the notebook.
+fn is_current_tab(notebook: &Notebook, webview: &webkit2gtk::WebView) -> bool {
+    let index = notebook.page_num(webview);
+    index.is_some() && index == notebook.current_page()
+}
+
+/// Get the title of a tab: the title of the page or its URL if it has no title.
+fn tab_title(webview: &webkit2gtk::WebView) -> String {
+    webview.title()
+        .filter(|title| !title.is_empty())
+        .or_else(|| webview.uri())
+        .map(Into::into)
+        .unwrap_or_default()
+}
+
+/// Get the position where to move the current tab.
+/// The position is either relative to the current one, like +1 or -2, or absolute, starting at 1.
+fn tab_position(position: &str, current: usize, tab_count: usize) -> Option<usize> {
+    let position = position.trim();
+    let new_position =
+        if position.starts_with('+') {
+            current.checked_add(position[1..].parse().ok()?)?
+        }
+        else if position.starts_with('-') {
+            current.checked_sub(position[1..].parse().ok()?)?
+        }
+        else {
+            position.parse::<usize>().ok()?.checked_sub(1)?
+        };
+    if new_position < tab_count {
+        Some(new_position)
+    }
+    else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tab_position;
+
+    #[test]
+    fn absolute_tab_position() {
+        assert_eq!(tab_position("1", 2, 3), Some(0));
+        assert_eq!(tab_position("3", 0, 3), Some(2));
+        assert_eq!(tab_position("0", 0, 3), None);
+        assert_eq!(tab_position("4", 0, 3), None);
+        assert_eq!(tab_position("tab", 0, 3), None);
+    }
+
+    #[test]
+    fn relative_tab_position() {
+        assert_eq!(tab_position("+1", 0, 3), Some(1));
+        assert_eq!(tab_position("-2", 2, 3), Some(0));
+        assert_eq!(tab_position("-1", 0, 3), None);
+        assert_eq!(tab_position("+2", 1, 3), None);
+    }
+}
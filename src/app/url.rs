@@ -24,7 +24,7 @@ use webkit2gtk::WebViewExt;
 use nuon_common::FollowMode;
 
 use app::App;
-use app::Msg::CreateWindow;
+use app::Msg::{CreatePrivateWindow, CreateWindow};
 use message_server::Privacy;
 use webview::Msg::PageOpen;
 use url::{Url, Position};
@@ -32,24 +32,55 @@ use urls::offset;
 
 impl App {
     /// Open the given URL in the web view.
+    /// The URL is opened in a new window if it is pinned to another container.
     pub fn open(&self, url: &str) {
         let url = self.transform_url(url);
-        self.components.webview.emit(PageOpen(url));
+        if let Some(privacy) = self.model.site_privacy.redirected_privacy(&url) {
+            self.model.relm.stream().emit(CreateWindow(url, privacy));
+        }
+        else {
+            self.current_tab().emit(PageOpen(url));
+        }
     }
 
     /// Open the given URL in a new window.
+    /// The new window stays private or in the same container as this one, unless the URL is
+    /// a private site or is pinned to another container.
     pub fn open_in_new_window(&self, url: &str, privacy: Privacy) {
+        let url = self.transform_url(url);
         let privacy =
-            if self.widgets.webview.is_ephemeral() {
+            if self.model.privacy == Privacy::Private || self.model.site_privacy.is_private_site(&url) {
                 Privacy::Private
             }
+            else if let Some(container) = self.model.site_privacy.pinned_container(&url) {
+                container
+            }
+            else if privacy == Privacy::Normal {
+                self.model.privacy.clone()
+            }
             else {
                 privacy
             };
-        let url = self.transform_url(url);
         self.model.relm.stream().emit(CreateWindow(url, privacy));
     }
 
+    /// Open the given URL in a new tab if the open-in-tab setting is set, in a new window otherwise.
+    pub fn open_in_new_page(&mut self, url: &str) {
+        if self.model.open_in_tab && self.model.site_privacy.redirected_privacy(url).is_none() {
+            self.tab_open(Some(url));
+        }
+        else {
+            self.open_in_new_window(url, Privacy::Normal);
+        }
+    }
+
+    /// Open the given URL in a new private window, with a new web context if private windows do not
+    /// share one.
+    pub fn private_win_open(&self, url: &str) {
+        let url = self.transform_url(url);
+        self.model.relm.stream().emit(CreatePrivateWindow(url));
+    }
+
     /// Open in a new window the url from the system clipboard.
     pub fn win_paste_url(&mut self) {
         if let Some(url) = self.get_url_from_clipboard() {
@@ -60,7 +91,7 @@ impl App {
     /// Go up one directory in url.
     pub fn go_parent_directory(&self, parent_level: Option<u32>) {
         let parent_level = parent_level.unwrap_or(1);
-        if let Some(ref url) = self.widgets.webview.uri() {
+        if let Some(ref url) = self.webview().uri() {
             if let Ok(mut url) = Url::parse(url) {
                 match url.path_segments_mut() {
                     Ok(mut segments) => {
@@ -78,7 +109,7 @@ impl App {
 
     /// Go to the root directory or url hostname.
     pub fn go_root_directory(&self) {
-        if let Some(ref url) = self.widgets.webview.uri() {
+        if let Some(ref url) = self.webview().uri() {
             if let Ok(base_url) = Url::parse(url) {
                 let root = &base_url[..Position::BeforePath];
 
@@ -97,7 +128,7 @@ impl App {
     }
 
     pub fn url_increment(&self) {
-        if let Some(ref url) = self.widgets.webview.uri() {
+        if let Some(ref url) = self.webview().uri() {
             if let Some(url) = offset(url, 1) {
                 self.open(&url);
             }
@@ -105,7 +136,7 @@ impl App {
     }
 
     pub fn url_decrement(&self) {
-        if let Some(ref url) = self.widgets.webview.uri() {
+        if let Some(ref url) = self.webview().uri() {
             if let Some(url) = offset(url, -1) {
                 self.open(&url);
             }
@@ -32,7 +32,9 @@ impl UserAgentManager {
     pub fn add(&self, name: &str) {
         MANAGER.with(|key| {
             let mut names = key.take();
-            names.push(name.to_string());
+            if !names.iter().any(|previous| previous == name) {
+                names.push(name.to_string());
+            }
             key.set(names);
         })
     }
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Management of the other windows of the application.
+
+use gtk::traits::{GtkWindowExt, NotebookExt};
+use webkit2gtk::WebViewExt;
+
+use app::App;
+use app::Msg::{CreateWindow, RestoreWindow};
+use message_server::Privacy;
+use session::SessionWindow;
+use windows::ClosedWindow;
+
+impl App {
+    /// Show the window having the specified identifier, or else containing the query in its title or URL.
+    pub fn buffer(&self, query: &str) {
+        match self.model.opened_windows.find(query) {
+            Some(window) => window.window.present(),
+            None => self.error(&format!("No window matching {}", query)),
+        }
+    }
+
+    /// Get the information about this window to remember it after it is closed.
+    pub fn closed_window(&self) -> ClosedWindow {
+        let title = self.webview().title()
+            .map(Into::into)
+            .unwrap_or_default();
+        ClosedWindow {
+            current_tab: self.widgets.notebook.current_page().unwrap_or(0) as usize,
+            privacy: self.privacy(),
+            tabs: self.session_tabs(),
+            title,
+            url: self.model.current_url.clone(),
+        }
+    }
+
+    /// Get whether this window is private or in a container.
+    pub fn privacy(&self) -> Privacy {
+        self.model.privacy.clone()
+    }
+
+    /// Reopen a closed window with its tabs and privacy.
+    fn reopen_window(&self, window: ClosedWindow) {
+        match SessionWindow::new(window.privacy.clone(), window.tabs, window.current_tab) {
+            Some(session_window) => self.model.relm.stream().emit(RestoreWindow(session_window)),
+            None => self.model.relm.stream().emit(CreateWindow(window.url, window.privacy)),
+        }
+    }
+
+    /// Reopen the `count` last closed windows.
+    pub fn undo_close(&self, count: Option<u32>) {
+        for _ in 0..count.unwrap_or(1) {
+            match self.model.closed_windows.pop() {
+                Some(window) => self.reopen_window(window),
+                None => {
+                    self.info("No more closed windows".to_string());
+                    break;
+                },
+            }
+        }
+    }
+
+    /// Reopen the closed window with the specified URL.
+    pub fn undo_close_select(&self, url: &str) {
+        match self.model.closed_windows.remove(url) {
+            Some(window) => self.reopen_window(window),
+            None => self.error(&format!("No closed window with the URL {}", url)),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Export of the bookmarks to files that can be imported in other browsers.
+//!
+//! Two formats are supported: the Netscape bookmark HTML file, which most browsers can import,
+//! and a JSON file, used when the file has the `.json` extension.
+//! The JSON file looks like this:
+//!
+//! ```json
+//! {
+//!   "bookmarks": [
+//!     {
+//!       "tags": ["rust", "programming"],
+//!       "title": "Rust Programming Language",
+//!       "url": "https://www.rust-lang.org/"
+//!     }
+//!   ],
+//!   "format": "nuon-bookmarks",
+//!   "version": 1
+//! }
+//! ```
+//!
+//! Both files can be imported back with the `bookmark-import` command, but only the JSON file
+//! keeps the tags as is.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde_json;
+
+use app::App;
+use bookmarks::{BookmarkManager, TaggedBookmark};
+use config_dir::ConfigDir;
+use errors::{Error, Result};
+
+/// The identifier of the JSON format.
+pub const JSON_FORMAT: &str = "nuon-bookmarks";
+/// The version of the JSON format.
+pub const JSON_VERSION: u32 = 1;
+
+/// The content of a JSON bookmark file.
+#[derive(Deserialize, Serialize)]
+pub struct BookmarkFile {
+    pub bookmarks: Vec<TaggedBookmark>,
+    pub format: String,
+    pub version: u32,
+}
+
+/// Write the bookmarks to a file, in JSON if its extension is `.json`, in Netscape HTML
+/// otherwise.
+pub fn export(path: &Path, bookmarks: Vec<TaggedBookmark>) -> Result<()> {
+    let is_json = path.extension().map(|extension| extension == "json").unwrap_or(false);
+    let content =
+        if is_json {
+            to_json(bookmarks)?
+        }
+        else {
+            to_netscape(&bookmarks)
+        };
+    let mut file = File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Export the bookmarks of the configuration directory without starting the GUI.
+/// Returns the number of exported bookmarks.
+pub fn export_from_config(config: &Option<String>, profile: &Option<String>, path: &Path) -> Result<usize> {
+    let config_dir = ConfigDir::new(config, profile)
+        .map_err(|error| Error::from_string(error.to_string()))?;
+    let bookmark_manager = BookmarkManager::open(&App::bookmark_path(&config_dir)?)?;
+    let bookmarks = bookmark_manager.export()?;
+    let count = bookmarks.len();
+    export(path, bookmarks)?;
+    Ok(count)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Convert the bookmarks to the nuon JSON format.
+fn to_json(bookmarks: Vec<TaggedBookmark>) -> Result<String> {
+    let file = BookmarkFile {
+        bookmarks,
+        format: JSON_FORMAT.to_string(),
+        version: JSON_VERSION,
+    };
+    Ok(format!("{}\n", serde_json::to_string_pretty(&file)?))
+}
+
+/// Convert the bookmarks to the Netscape bookmark HTML format.
+/// The tags are written in the TAGS attribute, like Firefox does.
+fn to_netscape(bookmarks: &[TaggedBookmark]) -> String {
+    let mut content = String::from("<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+");
+    for bookmark in bookmarks {
+        let tags =
+            if bookmark.tags.is_empty() {
+                String::new()
+            }
+            else {
+                format!(" TAGS=\"{}\"", escape_html(&bookmark.tags.join(",")))
+            };
+        content.push_str(&format!("    <DT><A HREF=\"{}\"{}>{}</A>\n", escape_html(&bookmark.url), tags,
+            escape_html(&bookmark.title)));
+    }
+    content.push_str("</DL><p>\n");
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use bookmark_import::{parse_json, parse_netscape};
+    use bookmarks::TaggedBookmark;
+    use super::{to_json, to_netscape};
+
+    fn bookmarks() -> Vec<TaggedBookmark> {
+        vec![
+            TaggedBookmark {
+                tags: vec!["programming".to_string(), "rust".to_string()],
+                title: "Rust <Programming> & \"Language\"".to_string(),
+                url: "https://www.rust-lang.org/?a=1&b=2".to_string(),
+            },
+            TaggedBookmark {
+                tags: vec![],
+                title: "GTK".to_string(),
+                url: "https://www.gtk.org/".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn json_round_trip() {
+        assert_eq!(parse_json(&to_json(bookmarks()).unwrap()).unwrap(), bookmarks());
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        assert_eq!(parse_netscape(&to_netscape(&bookmarks())), bookmarks());
+    }
+}
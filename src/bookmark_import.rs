@@ -0,0 +1,390 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Import of the bookmarks exported by other browsers.
+//!
+//! The supported formats are the Netscape bookmark HTML file, the Chromium `Bookmarks` JSON file,
+//! the Firefox `places.sqlite` database and the JSON file exported by nuon.
+//! The folders are converted to tags.
+
+use std::collections::HashMap;
+use std::fs::{File, copy};
+use std::io::Read;
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde_json::{self, Value};
+use tempfile;
+
+use bookmark_export::{BookmarkFile, JSON_FORMAT, JSON_VERSION};
+use bookmarks::TaggedBookmark;
+use errors::{Error, Result};
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// The GUIDs of the Firefox root folders, which are not converted to tags.
+const FIREFOX_ROOTS: &[&str] = &["root________", "menu________", "toolbar_____", "unfiled_____", "mobile______"];
+/// The GUID of the Firefox folder containing the tags.
+const FIREFOX_TAGS_ROOT: &str = "tags________";
+
+/// Read the bookmarks from a file, detecting its format.
+/// The bookmarks having the same URL are merged.
+pub fn import(path: &Path) -> Result<Vec<TaggedBookmark>> {
+    let mut content = vec![];
+    File::open(path)?.read_to_end(&mut content)?;
+    let bookmarks =
+        if content.starts_with(SQLITE_HEADER) {
+            parse_firefox(path)?
+        }
+        else {
+            let content = String::from_utf8_lossy(&content);
+            if content.trim_start().starts_with('{') {
+                parse_json(&content)?
+            }
+            else if content.to_lowercase().contains("<dl") {
+                parse_netscape(&content)
+            }
+            else {
+                return Err(Error::from_string(format!("Unknown bookmark file format: {}", path.display())));
+            }
+        };
+    Ok(merge(bookmarks))
+}
+
+/// Add a bookmark, merging it with the bookmark having the same URL, if any.
+fn add_bookmark(bookmarks: &mut Vec<TaggedBookmark>, indexes: &mut HashMap<String, usize>, bookmark: TaggedBookmark) {
+    if let Some(&index) = indexes.get(&bookmark.url) {
+        let existing = &mut bookmarks[index];
+        if existing.title.is_empty() {
+            existing.title = bookmark.title;
+        }
+        for tag in bookmark.tags {
+            if !existing.tags.contains(&tag) {
+                existing.tags.push(tag);
+            }
+        }
+    }
+    else {
+        indexes.insert(bookmark.url.clone(), bookmarks.len());
+        bookmarks.push(bookmark);
+    }
+}
+
+/// Get the value of an attribute in an HTML start tag.
+/// Only the ASCII letters are lowercased so that the positions in the lowercase tag match those in `tag`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower_tag = tag.to_ascii_lowercase();
+    let pattern = format!(" {}=\"", name);
+    let start = lower_tag.find(&pattern)? + pattern.len();
+    let end = start + tag[start..].find('"')?;
+    Some(unescape_html(&tag[start..end]))
+}
+
+/// Check if the URL can be opened in a web page, excluding URLs like the Firefox smart folders.
+fn is_web_url(url: &str) -> bool {
+    !url.is_empty() && !url.starts_with("place:") && !url.starts_with("javascript:")
+}
+
+/// Merge the bookmarks having the same URL.
+fn merge(bookmarks: Vec<TaggedBookmark>) -> Vec<TaggedBookmark> {
+    let mut merged = vec![];
+    let mut indexes = HashMap::new();
+    for bookmark in bookmarks {
+        add_bookmark(&mut merged, &mut indexes, bookmark);
+    }
+    merged
+}
+
+/// Parse the Chromium `Bookmarks` JSON file.
+fn parse_chromium(json: &Value) -> Result<Vec<TaggedBookmark>> {
+    let roots = json.get("roots")
+        .and_then(Value::as_object)
+        .ok_or_else(|| Error::new("Invalid Chromium bookmark file: no roots"))?;
+    let mut bookmarks = vec![];
+    // The root folders (bookmark bar, other bookmarks, …) are not converted to tags.
+    for root in roots.values() {
+        parse_chromium_children(root, &mut vec![], &mut bookmarks);
+    }
+    Ok(bookmarks)
+}
+
+fn parse_chromium_children(folder: &Value, folders: &mut Vec<String>, bookmarks: &mut Vec<TaggedBookmark>) {
+    let children = folder.get("children").and_then(Value::as_array);
+    for child in children.into_iter().flatten() {
+        let name = child.get("name").and_then(Value::as_str).unwrap_or_default();
+        match child.get("type").and_then(Value::as_str) {
+            Some("folder") => {
+                folders.push(folder_tag(name));
+                parse_chromium_children(child, folders, bookmarks);
+                folders.pop();
+            },
+            Some("url") => {
+                let url = child.get("url").and_then(Value::as_str).unwrap_or_default();
+                if is_web_url(url) {
+                    bookmarks.push(TaggedBookmark {
+                        tags: folders.clone(),
+                        title: name.to_string(),
+                        url: url.to_string(),
+                    });
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Parse the Firefox `places.sqlite` database.
+/// The database is copied first since it is locked while Firefox is running.
+fn parse_firefox(path: &Path) -> Result<Vec<TaggedBookmark>> {
+    let directory = tempfile::tempdir()?;
+    let database_path = directory.path().join("places.sqlite");
+    copy(path, &database_path)?;
+    // The recent changes might only be in the write-ahead log.
+    let mut wal_path = path.as_os_str().to_os_string();
+    wal_path.push("-wal");
+    if Path::new(&wal_path).exists() {
+        copy(&wal_path, directory.path().join("places.sqlite-wal"))?;
+    }
+    let connection = Connection::open(&database_path)?;
+
+    let mut folders = HashMap::new();
+    {
+        let mut statement = connection.prepare("
+            SELECT id, parent, COALESCE(title, ''), guid
+            FROM moz_bookmarks
+            WHERE type = 2
+        ")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let parent: i64 = row.get(1)?;
+            let title: String = row.get(2)?;
+            let guid: String = row.get(3)?;
+            folders.insert(id, (parent, title, guid));
+        }
+    }
+
+    let mut statement = connection.prepare("
+        SELECT moz_bookmarks.parent, COALESCE(moz_bookmarks.title, moz_places.title, ''), moz_places.url
+        FROM moz_bookmarks
+        INNER JOIN moz_places
+            ON moz_bookmarks.fk = moz_places.id
+        WHERE moz_bookmarks.type = 1
+        ORDER BY moz_bookmarks.position
+    ")?;
+    let mut rows = statement.query([])?;
+    let mut bookmarks = vec![];
+    while let Some(row) = rows.next()? {
+        let mut parent: i64 = row.get(0)?;
+        let title: String = row.get(1)?;
+        let url: String = row.get(2)?;
+        if !is_web_url(&url) {
+            continue;
+        }
+
+        let mut tags = vec![];
+        let mut is_tag = false;
+        while let Some(&(grand_parent, ref name, ref guid)) = folders.get(&parent) {
+            if guid == FIREFOX_TAGS_ROOT {
+                is_tag = true;
+                break;
+            }
+            if !FIREFOX_ROOTS.contains(&guid.as_str()) {
+                tags.push(folder_tag(name));
+            }
+            parent = grand_parent;
+        }
+        tags.reverse();
+
+        // A bookmark in the tags folder only indicates that the URL has this tag: the tag is the
+        // name of its folder.
+        bookmarks.push(TaggedBookmark {
+            tags,
+            title: if is_tag { String::new() } else { title },
+            url,
+        });
+    }
+    Ok(bookmarks)
+}
+
+/// Parse a JSON bookmark file, either exported by nuon or by Chromium.
+pub fn parse_json(content: &str) -> Result<Vec<TaggedBookmark>> {
+    let json: Value = serde_json::from_str(content)
+        .map_err(|error| Error::from_string(format!("Invalid bookmark file: {}", error)))?;
+    if json.get("format").and_then(Value::as_str) == Some(JSON_FORMAT) {
+        let file: BookmarkFile = serde_json::from_value(json)?;
+        if file.version > JSON_VERSION {
+            return Err(Error::from_string(format!("Unsupported bookmark file version: {}", file.version)));
+        }
+        Ok(file.bookmarks)
+    }
+    else {
+        parse_chromium(&json)
+    }
+}
+
+/// Parse the Netscape bookmark HTML file.
+pub fn parse_netscape(content: &str) -> Vec<TaggedBookmark> {
+    let mut bookmarks = vec![];
+    // The folder of each opened <DL>: None for the root folders which are not converted to tags.
+    let mut folders: Vec<Option<String>> = vec![];
+    let mut folder = None;
+    let mut rest = content;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let end =
+            match rest.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+        let tag = &rest[..=end];
+        rest = &rest[end + 1..];
+        let tag_name = tag[1..].split(|char: char| char.is_whitespace() || char == '>').next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match tag_name.as_str() {
+            "a" => {
+                let title = text_until(rest, "</");
+                let url = attribute(tag, "href").unwrap_or_default();
+                if is_web_url(&url) {
+                    let mut tags: Vec<_> = folders.iter().filter_map(|folder| folder.clone()).collect();
+                    if let Some(extra_tags) = attribute(tag, "tags") {
+                        tags.extend(extra_tags.split(',').map(folder_tag).filter(|tag| !tag.is_empty()));
+                    }
+                    bookmarks.push(TaggedBookmark {
+                        tags,
+                        title,
+                        url,
+                    });
+                }
+            },
+            "h3" => {
+                let is_root = attribute(tag, "personal_toolbar_folder").is_some() ||
+                    attribute(tag, "unfiled_bookmarks_folder").is_some();
+                folder = Some(if is_root { None } else { Some(folder_tag(&text_until(rest, "</"))) });
+            },
+            "dl" => folders.push(folder.take().unwrap_or(None)),
+            "/dl" => {
+                folders.pop();
+            },
+            _ => (),
+        }
+    }
+    bookmarks
+}
+
+/// Convert a folder name to a tag, which cannot contain spaces.
+fn folder_tag(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// Get the unescaped text until the specified delimiter.
+fn text_until(text: &str, delimiter: &str) -> String {
+    let end = text.find(delimiter).unwrap_or(text.len());
+    unescape_html(text[..end].trim())
+}
+
+/// Replace the HTML entities used in the bookmark files by their character.
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use bookmarks::TaggedBookmark;
+    use super::{attribute, parse_json, parse_netscape};
+
+    fn bookmark(title: &str, url: &str, tags: &[&str]) -> TaggedBookmark {
+        TaggedBookmark {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            title: title.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn chromium() {
+        let content = r#"{
+            "roots": {
+                "bookmark_bar": {
+                    "children": [
+                        { "name": "Rust", "type": "url", "url": "https://www.rust-lang.org/" },
+                        {
+                            "children": [
+                                { "name": "GTK", "type": "url", "url": "https://www.gtk.org/" }
+                            ],
+                            "name": "Web Dev",
+                            "type": "folder"
+                        }
+                    ],
+                    "name": "Bookmarks bar",
+                    "type": "folder"
+                }
+            },
+            "version": 1
+        }"#;
+        assert_eq!(parse_json(content).unwrap(), vec![
+            bookmark("Rust", "https://www.rust-lang.org/", &[]),
+            bookmark("GTK", "https://www.gtk.org/", &["web-dev"]),
+        ]);
+    }
+
+    #[test]
+    fn netscape() {
+        let content = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1500000000">Rust &amp; Cargo</A>
+        <DT><H3>Web Dev</H3>
+        <DL><p>
+            <DT><A HREF="https://www.gtk.org/" TAGS="gui,toolkit">GTK</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="place:sort=8">Recent Tags</A>
+    <DT><A HREF="https://webkitgtk.org/">WebKitGTK</A>
+</DL><p>"#;
+        assert_eq!(parse_netscape(content), vec![
+            bookmark("Rust & Cargo", "https://www.rust-lang.org/", &[]),
+            bookmark("GTK", "https://www.gtk.org/", &["web-dev", "gui", "toolkit"]),
+            bookmark("WebKitGTK", "https://webkitgtk.org/", &[]),
+        ]);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(attribute(r#"<A HREF="https://example.com/" TAGS="a,b">"#, "href"),
+            Some("https://example.com/".to_string()));
+        assert_eq!(attribute(r#"<a TITLE="İstanbul" HREF="https://example.com/">"#, "href"),
+            Some("https://example.com/".to_string()));
+        assert_eq!(attribute(r#"<a title="ẞ&amp;ẞ">"#, "title"), Some("ẞ&ẞ".to_string()));
+        assert_eq!(attribute("<a>", "href"), None);
+    }
+}
@@ -19,21 +19,40 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-//! Bookmark management.
+//! Bookmark, browsing history and mark management.
 
-use std::cell::RefCell;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
 use std::result;
 
-use rusqlite::Connection;
+use nuon_common::Position;
+use rusqlite::{Connection, OptionalExtension};
 use rusqlite::types::ToSql;
 
 use errors::{Error, Result};
+use migrations;
 
-thread_local! {
-    static CONNECTION: RefCell<Option<Connection>> = RefCell::new(None);
-}
+/// The maximum number of history entries shown in the completions.
+const HISTORY_COMPLETION_LIMIT: u32 = 100;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// The weight given to a visit, according to its age in days.
+/// This is used to rank the completions by frecency: the pages visited often and recently come
+/// first.
+const RECENCY_WEIGHTS: &[(u32, u32)] = &[
+    (4, 100),
+    (14, 70),
+    (31, 50),
+    (90, 30),
+];
+const OLD_VISIT_WEIGHT: u32 = 10;
+
+/// The BM25 weights of the title, URL and description columns of the bookmark full-text index.
+const BOOKMARK_COLUMN_WEIGHTS: &str = "10.0, 5.0, 1.0";
+/// The BM25 weights of the title and URL columns of the history full-text index.
+const HISTORY_COLUMN_WEIGHTS: &str = "10.0, 5.0";
 
 /// A bookmark has a title and a URL and optionally some tags.
 #[derive(Debug)]
@@ -54,106 +73,134 @@ impl Bookmark {
     }
 }
 
+/// A bookmark with its list of tags, as read from or written to a bookmark file.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct TaggedBookmark {
+    pub tags: Vec<String>,
+    pub title: String,
+    pub url: String,
+}
+
+/// An entry of the browsing history.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub url: String,
+}
+
 /// A bookmark manager is use to add, search and remove bookmarks.
+/// It also records the browsing history, which is stored in the same database.
+/// The clones of a bookmark manager share the same database connection.
+#[derive(Clone)]
 pub struct BookmarkManager {
+    connection: Rc<Connection>,
+    /// Whether the database is in memory, so that nothing is saved when the application exits.
+    temporary: bool,
 }
 
 impl BookmarkManager {
-    /// Create a new bookmark manager.
-    pub fn new() -> Self {
-        BookmarkManager {
-        }
+    /// Open the database, which can be `:memory:` for a temporary in-memory database.
+    /// The schema of the database is upgraded to the latest version.
+    pub fn open(filename: &Path) -> Result<Self> {
+        let connection = Connection::open(filename)?;
+        // Activate foreign key contraints in SQLite.
+        connection.execute("PRAGMA foreign_keys = ON", [])?;
+        migrations::migrate(&connection, filename)?;
+        Ok(BookmarkManager {
+            connection: Rc::new(connection),
+            temporary: filename == Path::new(":memory:"),
+        })
     }
 
     /// Add a bookmark.
     /// Returns true if the bookmark was added.
     pub fn add(&self, url: String, title: Option<String>) -> Result<bool> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                if let Ok(inserted_count) = connection.execute("
-                    INSERT INTO bookmarks (title, url)
-                    VALUES ($1, $2)
-                    ", &[&title.unwrap_or_default(), &url])
-                {
-                    return Ok(inserted_count > 0);
-                }
-            }
-            Ok(false)
-        })
+        let inserted_count = self.connection.execute("
+            INSERT OR IGNORE INTO bookmarks (title, url)
+            VALUES ($1, $2)
+            ", &[&title.unwrap_or_default(), &url])?;
+        Ok(inserted_count > 0)
     }
 
-    /// Connect to the database if it is not already connected.
-    pub fn connect(&self, filename: PathBuf) -> Result<()> {
-        CONNECTION.with(|connection| {
-            let mut connection = connection.borrow_mut();
-            if connection.is_none() {
-                let db = Connection::open(filename)?;
-                // Activate foreign key contraints in SQLite.
-                db.execute("PRAGMA foreign_keys = ON", [])?;
-                *connection = Some(db);
-            }
-            Ok(())
-        })
+    /// Add a global mark, replacing the page it previously pointed to, if any.
+    pub fn add_global_mark(&self, mark: u8, url: &str, position: &Position) -> Result<()> {
+        self.connection.execute("
+            INSERT OR REPLACE INTO global_marks (mark, url, anchor, anchor_offset, percentage)
+            VALUES ($1, $2, $3, $4, $5)
+            ", &[&mark as &dyn ToSql, &url.to_string(), &position.anchor, &position.offset,
+                &position.percentage])?;
+        Ok(())
     }
 
-    /// Create the SQL tables for the bookmarks.
-    pub fn create_tables(&self) -> Result<()> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                connection.execute("
-                CREATE TABLE IF NOT EXISTS bookmarks
-                ( id INTEGER PRIMARY KEY
-                , title TEXT NOT NULL
-                , url TEXT NOT NULL UNIQUE
-                , visit_count INTEGER NOT NULL DEFAULT 0
-                )", [])?;
-
-                connection.execute("
-                CREATE TABLE IF NOT EXISTS tags
-                ( id INTEGER PRIMARY KEY
-                , name TEXT NOT NULL UNIQUE
-                )", [])?;
-
-                connection.execute("
-                CREATE TABLE IF NOT EXISTS bookmarks_tags
-                ( bookmark_id INTEGER NOT NULL
-                , tag_id INTEGER NOT NULL
-                , PRIMARY KEY (bookmark_id, tag_id)
-                , FOREIGN KEY(bookmark_id) REFERENCES bookmarks(id) ON DELETE CASCADE
-                , FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
-                )", [])?;
-            }
-            Ok(())
-        })
+    /// Add a mark to a page, replacing the previous one with the same name.
+    pub fn add_mark(&self, url: &str, mark: u8, position: &Position) -> Result<()> {
+        self.connection.execute("
+            INSERT OR REPLACE INTO marks (url, mark, anchor, anchor_offset, percentage)
+            VALUES ($1, $2, $3, $4, $5)
+            ", &[&url.to_string() as &dyn ToSql, &mark, &position.anchor, &position.offset,
+                &position.percentage])?;
+        Ok(())
+    }
+
+    /// Add tags to a bookmark, keeping its existing tags.
+    fn add_tags(&self, bookmark_id: i32, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            let tag = tag.to_lowercase();
+            self.connection.execute("
+                INSERT OR IGNORE INTO tags (name)
+                VALUES ($1)
+            ", &[&tag])?;
+            let tag_id = self.get_tag_id(&tag)?;
+            self.connection.execute("
+                INSERT OR IGNORE INTO bookmarks_tags (bookmark_id, tag_id)
+                VALUES ($1, $2)
+            ", &[&bookmark_id, &tag_id])?;
+        }
+        Ok(())
+    }
+
+    /// Add a visit to the browsing history and to the bookmark of this page, if any.
+    pub fn add_visit(&self, url: &str, title: Option<String>) -> Result<()> {
+        self.connection.execute("
+            INSERT OR IGNORE INTO history (title, url)
+            VALUES ($1, $2)
+            ", &[&title.clone().unwrap_or_default(), &url.to_string()])?;
+        if let Some(title) = title {
+            self.update_history_title(url, &title)?;
+        }
+        self.connection.execute("
+            INSERT INTO history_visits (history_id, visited_at)
+            SELECT id, CAST(strftime('%s', 'now') AS INTEGER)
+            FROM history
+            WHERE url = $1
+            ", &[&url.to_string()])?;
+        self.connection.execute("
+            UPDATE bookmarks
+            SET visit_count = visit_count + 1
+              , last_visit = CAST(strftime('%s', 'now') AS INTEGER)
+            WHERE url = $1
+            ", &[&url.to_string()])?;
+        Ok(())
     }
 
     /// Delete a bookmark.
     /// Returns true if a bookmark was deleted.
     pub fn delete(&self, url: &str) -> Result<bool> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                if let Ok(deleted_count) = connection.execute("
-                    DELETE FROM bookmarks
-                    WHERE url = $1
-                    ", &[&url.to_string()])
-                {
-                    return Ok(deleted_count > 0);
-                }
-            }
-            Ok(false)
-        })
+        let deleted_count = self.connection.execute("
+            DELETE FROM bookmarks
+            WHERE url = $1
+            ", &[&url.to_string()])?;
+        Ok(deleted_count > 0)
     }
 
     /// Delete the tags that are in `original_tags` but not in `tags`.
-    fn delete_tags(&self, connection: &Connection, bookmark_id: i32, original_tags: &[String], tags: &[String])
-        -> Result<()>
-    {
+    fn delete_tags(&self, bookmark_id: i32, original_tags: &[String], tags: &[String]) -> Result<()> {
         let original_tags: HashSet<_> = original_tags.iter().collect();
         let tags: HashSet<_> = tags.iter().collect();
         let tags_to_delete = &original_tags - &tags;
         for tag in tags_to_delete {
-            let tag_id = self.get_tag_id(connection, tag)?;
-            connection.execute("
+            let tag_id = self.get_tag_id(tag)?;
+            self.connection.execute("
                 DELETE FROM bookmarks_tags
                 WHERE bookmark_id = $1 AND tag_id = $2
             ", &[&bookmark_id, &tag_id])?;
@@ -162,183 +209,306 @@ impl BookmarkManager {
     }
 
     /// Check if a bookmark exists.
-    pub fn exists(&self, url: &str) -> bool {
-        self.get_id(url).is_some()
+    pub fn exists(&self, url: &str) -> Result<bool> {
+        Ok(self.get_id(url)?.is_some())
+    }
+
+    /// Get all the bookmarks with their tags, ordered by title.
+    pub fn export(&self) -> Result<Vec<TaggedBookmark>> {
+        let mut statement = self.connection.prepare("
+            SELECT bookmark_id, name
+            FROM bookmarks_tags
+            INNER JOIN tags
+                ON bookmarks_tags.tag_id = tags.id
+            ORDER BY name
+        ")?;
+        let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            let (bookmark_id, tag) = row?;
+            tags.entry(bookmark_id).or_insert_with(Vec::new).push(tag);
+        }
+
+        let mut statement = self.connection.prepare("
+            SELECT id, title, url
+            FROM bookmarks
+            ORDER BY title, url
+        ")?;
+        let rows = statement.query_map([], |row| {
+            let bookmark_id: i32 = row.get(0)?;
+            Ok(TaggedBookmark {
+                tags: tags.remove(&bookmark_id).unwrap_or_default(),
+                title: row.get(1)?,
+                url: row.get(2)?,
+            })
+        })?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
     }
 
     /// Get the id of a bookmark.
-    pub fn get_id(&self, url: &str) -> Option<i32> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                if let Ok(mut statement) = connection.prepare("
-                        SELECT id
-                        FROM bookmarks
-                        WHERE url = $1
-                    ")
-                {
-                    if let Ok(mut rows) = statement.query(&[&url.to_string()])
-                    {
-                        return rows.next().ok().and_then(|row| row.and_then(|row| row.get(0).ok()));
-                    }
-                }
-            }
-            None
-        })
+    pub fn get_id(&self, url: &str) -> Result<Option<i32>> {
+        let id = self.connection.query_row("
+            SELECT id
+            FROM bookmarks
+            WHERE url = $1
+            ", &[&url.to_string()], |row| row.get(0))
+            .optional()?;
+        Ok(id)
     }
 
     /// Get the tag ID of a bookmark.
-    pub fn get_tag_id(&self, connection: &Connection, tag: &str) -> Result<i32> {
-        let mut statement = connection.prepare("
+    fn get_tag_id(&self, tag: &str) -> Result<i32> {
+        let id = self.connection.query_row("
             SELECT id
             FROM tags
             WHERE name = $1
-        ")?;
-        let mut rows = statement.query(&[&tag.to_string()])?;
-        let row = rows.next()?;
-        let row = row.ok_or_else(|| Error::from_string("tag not found".to_string()))?;
-        Ok(row.get(0)?)
+            ", &[&tag.to_string()], |row| row.get(0))
+            .optional()?;
+        id.ok_or_else(|| Error::from_string("tag not found".to_string()))
     }
 
     /// Get the tags of a bookmark.
     pub fn get_tags(&self, url: &str) -> Result<Vec<String>> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                if let Ok(mut statement) = connection.prepare("
-                        SELECT name
-                        FROM tags
-                        INNER JOIN bookmarks_tags
-                            ON tags.id = bookmarks_tags.tag_id
-                        INNER JOIN bookmarks
-                            ON bookmarks_tags.bookmark_id = bookmarks.id
-                        WHERE url = $1
-                    ")
-                {
-                    if let Ok(rows) = statement.query_map(&[&url.to_string()], |row| {
-                            row.get(0)
-                        })
-                    {
-                        return rows.collect::<result::Result<Vec<_>, _>>()
-                            .map_err(Into::into);
-                    }
-                }
-            }
-            Ok(vec![])
-        })
+        let mut statement = self.connection.prepare("
+            SELECT name
+            FROM tags
+            INNER JOIN bookmarks_tags
+                ON tags.id = bookmarks_tags.tag_id
+            INNER JOIN bookmarks
+                ON bookmarks_tags.bookmark_id = bookmarks.id
+            WHERE url = $1
+        ")?;
+        let rows = statement.query_map(&[&url.to_string()], |row| row.get(0))?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Add the imported bookmarks.
+    /// A bookmark whose URL already exists is merged into the existing one: it gets the new tags
+    /// and its title if the existing one has none.
+    /// Returns the number of new bookmarks.
+    pub fn import(&self, bookmarks: &[TaggedBookmark]) -> Result<usize> {
+        let mut added_count = 0;
+        let transaction = self.connection.unchecked_transaction()?;
+        for bookmark in bookmarks {
+            added_count += self.connection.execute("
+                INSERT OR IGNORE INTO bookmarks (title, url)
+                VALUES ($1, $2)
+                ", &[&bookmark.title, &bookmark.url])?;
+            self.connection.execute("
+                UPDATE bookmarks
+                SET title = $1
+                WHERE url = $2 AND title = ''
+                ", &[&bookmark.title, &bookmark.url])?;
+            let bookmark_id = self.connection.query_row("
+                SELECT id
+                FROM bookmarks
+                WHERE url = $1
+                ", &[&bookmark.url], |row| row.get(0))?;
+            self.add_tags(bookmark_id, &bookmark.tags)?;
+        }
+        transaction.commit()?;
+        Ok(added_count)
+    }
+
+    /// Check whether the database is in memory and is lost when the application exits.
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+
+    /// Get the URL of the page a global mark points to.
+    pub fn global_mark_url(&self, mark: u8) -> Result<Option<String>> {
+        let url = self.connection.query_row("
+            SELECT url
+            FROM global_marks
+            WHERE mark = $1
+            ", &[&mark], |row| row.get(0))
+            .optional()?;
+        Ok(url)
+    }
+
+    /// Get the marks of a page, including the global marks pointing to it.
+    pub fn marks(&self, url: &str) -> Result<Vec<(u8, Position)>> {
+        let mut statement = self.connection.prepare("
+            SELECT mark, anchor, anchor_offset, percentage
+            FROM marks
+            WHERE url = $1
+            UNION ALL
+            SELECT mark, anchor, anchor_offset, percentage
+            FROM global_marks
+            WHERE url = $1
+        ")?;
+        let rows = statement.query_map(&[&url.to_string()], |row| {
+            Ok((row.get(0)?, Position {
+                anchor: row.get(1)?,
+                offset: row.get(2)?,
+                percentage: row.get(3)?,
+            }))
+        })?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
     }
 
     /// Query the bookmarks.
-    pub fn query(&self, input: BookmarkInput) -> Vec<Bookmark> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                let mut params: Vec<&dyn ToSql> = vec![];
-
-                let mut title_idents = vec![];
-                for title in &input.words {
-                    let index = params.len();
-                    title_idents.push(format!("(title LIKE '%' || ${} || '%' OR url LIKE '%' || ${} || '%')", index, index + 1));
-                    params.push(title);
-                    params.push(title);
-                }
-                let title_idents = title_idents.join(" AND ");
-                let where_clause =
-                    if !title_idents.is_empty() {
-                        format!("WHERE {}", title_idents)
-                    }
-                    else {
-                        String::new()
-                    };
-
-                let delta = params.len();
-                let mut tag_idents = vec![];
-                for (index, tag) in input.tags.iter().enumerate() {
-                    tag_idents.push(format!("tags.name LIKE ${} || '%'", index + delta));
-                    params.push(tag);
-                }
-                let tag_idents = tag_idents.join(" OR ");
-                let having_clause =
-                    if !tag_idents.is_empty() {
-                        format!("HAVING COUNT(CASE WHEN {} THEN 1 END) = {}", tag_idents, input.tags.len())
-                    }
-                    else {
-                        String::new()
-                    };
-
-                if let Ok(mut statement) = connection.prepare(&format!("
-                            SELECT title, url, COALESCE(GROUP_CONCAT(tags.name, ' #'), '')
-                            FROM bookmarks
-                            LEFT OUTER JOIN bookmarks_tags
-                                ON bookmarks.id = bookmarks_tags.bookmark_id
-                            LEFT OUTER JOIN tags
-                                ON bookmarks_tags.tag_id = tags.id
-                            {}
-                            GROUP BY url
-                            {}
-                        ", where_clause, having_clause))
-                {
-                    if let Ok(rows) = statement.query_map(&*params, |row| {
-                        if let (Ok(title), Ok(url), Ok(tags)) = (row.get(1), row.get(0), row.get(2)) {
-                            Ok(Some(Bookmark::new(title, url, tags)))
-                        }
-                        else {
-                            Ok(None)
-                        }
-                    })
-                    {
-                        return rows.filter_map(|row| row.transpose()).collect::<result::Result<Vec<_>, _>>().unwrap_or_else(|_| vec![]);
-                    }
-                }
+    /// The bookmarks matching the words are ranked by relevance, then by frecency.
+    pub fn query(&self, input: BookmarkInput) -> Result<Vec<Bookmark>> {
+        let mut params: Vec<&dyn ToSql> = vec![];
+
+        let match_query = full_text_query(&input.words);
+        let (matches_join, rank_order) =
+            if let Some(ref match_query) = match_query {
+                params.push(match_query);
+                (format!("
+                    INNER JOIN
+                        ( SELECT rowid, rank
+                          FROM bookmarks_fts
+                          WHERE bookmarks_fts MATCH $0 AND rank MATCH 'bm25({})'
+                        ) AS matches
+                        ON bookmarks.id = matches.rowid", BOOKMARK_COLUMN_WEIGHTS),
+                 "matches.rank, ")
             }
-            vec![]
-        })
+            else {
+                (String::new(), "")
+            };
+
+        let delta = params.len();
+        let mut tag_idents = vec![];
+        for (index, tag) in input.tags.iter().enumerate() {
+            tag_idents.push(format!("tags.name LIKE ${} || '%'", index + delta));
+            params.push(tag);
+        }
+        let tag_idents = tag_idents.join(" OR ");
+        let having_clause =
+            if !tag_idents.is_empty() {
+                format!("HAVING COUNT(CASE WHEN {} THEN 1 END) = {}", tag_idents, input.tags.len())
+            }
+            else {
+                String::new()
+            };
+
+        let mut statement = self.connection.prepare(&format!("
+            SELECT title, url, COALESCE(GROUP_CONCAT(tags.name, ' #'), '')
+            FROM bookmarks
+            {}
+            LEFT OUTER JOIN bookmarks_tags
+                ON bookmarks.id = bookmarks_tags.bookmark_id
+            LEFT OUTER JOIN tags
+                ON bookmarks_tags.tag_id = tags.id
+            GROUP BY url
+            {}
+            ORDER BY {}visit_count * {} DESC, title
+        ", matches_join, having_clause, rank_order, recency_weight("last_visit")))?;
+        let rows = statement.query_map(&*params, |row| {
+            Ok(Bookmark::new(row.get(1)?, row.get(0)?, row.get(2)?))
+        })?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
     }
 
-    pub fn search_tags(&self, tag_name: &str) -> Result<Vec<String>> {
-        CONNECTION.with(|connection| {
-            if let Some(ref connection) = *connection.borrow() {
-                if let Ok(mut statement) = connection.prepare("
-                        SELECT name
-                        FROM tags
-                        WHERE name LIKE '%' || $1 || '%'
-                    ")
-                {
-                    if let Ok(rows) = statement.query_map(&[&tag_name], |row| {
-                            row.get(0)
-                        })
-                    {
-                        return rows.collect::<result::Result<Vec<_>, _>>()
-                            .map_err(Into::into);
-                    }
-                }
+    /// Query the browsing history.
+    /// The pages matching the words are ranked by relevance, then by frecency.
+    pub fn query_history(&self, words: &[String]) -> Result<Vec<HistoryEntry>> {
+        let mut params: Vec<&dyn ToSql> = vec![];
+
+        let match_query = full_text_query(words);
+        let (matches_join, rank_order) =
+            if let Some(ref match_query) = match_query {
+                params.push(match_query);
+                (format!("
+                    INNER JOIN
+                        ( SELECT rowid, rank
+                          FROM history_fts
+                          WHERE history_fts MATCH $0 AND rank MATCH 'bm25({})'
+                        ) AS matches
+                        ON history.id = matches.rowid", HISTORY_COLUMN_WEIGHTS),
+                 "matches.rank, ")
             }
-            Ok(vec![])
-        })
+            else {
+                (String::new(), "")
+            };
+
+        let mut statement = self.connection.prepare(&format!("
+            SELECT title, url
+            FROM history
+            INNER JOIN history_visits
+                ON history.id = history_visits.history_id
+            {}
+            GROUP BY url
+            ORDER BY {}SUM({}) DESC, MAX(visited_at) DESC
+            LIMIT {}
+        ", matches_join, rank_order, recency_weight("visited_at"), HISTORY_COMPLETION_LIMIT))?;
+        let rows = statement.query_map(&*params, |row| {
+            Ok(HistoryEntry {
+                title: row.get(0)?,
+                url: row.get(1)?,
+            })
+        })?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    pub fn search_tags(&self, tag_name: &str) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare("
+            SELECT name
+            FROM tags
+            WHERE name LIKE '%' || $1 || '%'
+        ")?;
+        let rows = statement.query_map(&[&tag_name], |row| row.get(0))?;
+        rows.collect::<result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Update the title of a page in the browsing history, since it is often not known when the
+    /// visit is added.
+    pub fn update_history_title(&self, url: &str, title: &str) -> Result<()> {
+        if !title.is_empty() {
+            self.connection.execute("
+                UPDATE history
+                SET title = $1
+                WHERE url = $2
+                ", &[&title.to_string(), &url.to_string()])?;
+        }
+        Ok(())
     }
 
     /// Set the tags of a bookmark.
     pub fn set_tags(&self, url: &str, tags: Vec<String>) -> Result<()> {
         let original_tags = self.get_tags(url)?;
-        CONNECTION.with(|connection| {
-            if let Some(bookmark_id) = self.get_id(url) {
-                if let Some(ref connection) = *connection.borrow() {
-                    for tag in &tags {
-                        let tag = tag.to_lowercase();
-                        connection.execute("
-                            INSERT OR IGNORE INTO tags (name)
-                            VALUES ($1)
-                        ", &[&tag])?;
-                        let tag_id = self.get_tag_id(connection, &tag)?;
-                        connection.execute("
-                            INSERT OR IGNORE INTO bookmarks_tags (bookmark_id, tag_id)
-                            VALUES ($1, $2)
-                        ", &[&bookmark_id, &tag_id])
-                            .map(|_| ())?
-                    }
-                    self.delete_tags(connection, bookmark_id, &original_tags, &tags)?;
-                }
-            }
-            Ok(())
-        })
+        if let Some(bookmark_id) = self.get_id(url)? {
+            self.add_tags(bookmark_id, &tags)?;
+            self.delete_tags(bookmark_id, &original_tags, &tags)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the FTS5 query matching the rows containing all the words.
+/// Every word is a prefix, so that the results are updated while typing.
+/// Returns None when there is no word to search for.
+fn full_text_query(words: &[String]) -> Option<String> {
+    let terms: Vec<_> = words.iter()
+        // The tokenizer drops the punctuation, so a word without any letter or digit matches nothing.
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    }
+    else {
+        Some(terms.join(" AND "))
+    }
+}
+
+/// Get the SQL expression of the weight of a visit made at the time in `column`.
+fn recency_weight(column: &str) -> String {
+    let mut cases = format!("CASE WHEN {} IS NULL THEN 0", column);
+    for &(days, weight) in RECENCY_WEIGHTS {
+        cases.push_str(&format!(" WHEN CAST(strftime('%s', 'now') AS INTEGER) - {} < {} THEN {}", column,
+            days * SECONDS_PER_DAY, weight));
     }
+    cases.push_str(&format!(" ELSE {} END", OLD_VISIT_WEIGHT));
+    format!("({})", cases)
 }
 
 /// A bookmark input query.
@@ -346,3 +516,50 @@ pub struct BookmarkInput {
     pub tags: Vec<String>,
     pub words: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{BookmarkInput, BookmarkManager, full_text_query};
+
+    fn manager() -> BookmarkManager {
+        let manager = BookmarkManager::open(Path::new(":memory:")).unwrap();
+        manager.add("https://www.rust-lang.org/".to_string(), Some("Rust Programming Language".to_string())).unwrap();
+        manager.add("https://github.com/rust-lang".to_string(), Some("GitHub".to_string())).unwrap();
+        manager.add("https://trustworthy.example/".to_string(), Some("Example".to_string())).unwrap();
+        manager.set_tags("https://github.com/rust-lang", vec!["code".to_string()]).unwrap();
+        manager
+    }
+
+    fn query(manager: &BookmarkManager, words: &[&str], tags: &[&str]) -> Vec<String> {
+        let input = BookmarkInput {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            words: words.iter().map(|word| word.to_string()).collect(),
+        };
+        manager.query(input).unwrap().into_iter()
+            .map(|bookmark| bookmark.url)
+            .collect()
+    }
+
+    #[test]
+    fn full_text_query_words() {
+        assert_eq!(full_text_query(&[]), None);
+        assert_eq!(full_text_query(&["/".to_string()]), None);
+        assert_eq!(full_text_query(&["rust".to_string(), "a\"b".to_string()]),
+            Some("\"rust\"* AND \"a\"\"b\"*".to_string()));
+    }
+
+    #[test]
+    fn query_full_text() {
+        let manager = manager();
+        assert_eq!(query(&manager, &["rust"], &[]), vec!["https://www.rust-lang.org/", "https://github.com/rust-lang"]);
+        assert_eq!(query(&manager, &["prog"], &[]), vec!["https://www.rust-lang.org/"]);
+        assert_eq!(query(&manager, &["rust"], &["code"]), vec!["https://github.com/rust-lang"]);
+        assert_eq!(query(&manager, &["github.com"], &[]), vec!["https://github.com/rust-lang"]);
+        assert!(query(&manager, &["ust"], &[]).is_empty());
+
+        manager.delete("https://www.rust-lang.org/").unwrap();
+        assert_eq!(query(&manager, &["rust"], &[]), vec!["https://github.com/rust-lang"]);
+    }
+}
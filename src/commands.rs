@@ -37,12 +37,24 @@ pub enum AppCommand {
     BookmarkDel,
     #[help(text="Edit the bookmark tags of the current page")]
     BookmarkEditTags,
+    #[help(text="Export the bookmarks to a Netscape HTML or JSON (.json) file")]
+    BookmarkExport(String),
+    #[help(text="Import the bookmarks from a Netscape HTML, Chromium or Firefox file")]
+    BookmarkImport(String),
+    #[help(text="Switch to another window")]
+    Buffer(String),
     #[help(text="Clear the browser cache")]
     ClearCache,
     #[help(text="Try to click link to next page if it exists")]
     ClickNextPage,
     #[help(text="Try to click link to the previous page if it exists")]
     ClickPrevPage,
+    #[help(text="Reload the configuration files in every window")]
+    ConfigReload,
+    #[completion(hidden)]
+    ContainerDomain(String),
+    #[help(text="Open an URL in a new window of the specified container")]
+    ContainerOpen(String),
     #[completion(hidden)]
     CopyLinkUrl,
     #[completion(hidden)]
@@ -102,8 +114,12 @@ pub enum AppCommand {
     PreferredLanguage(String),
     #[help(text="Print the current page")]
     Print,
+    #[completion(hidden)]
+    PrivateSite(String),
     #[help(text="Open an URL in a new private window")]
     PrivateWinOpen(String),
+    #[help(text="Open a window with the specified profile")]
+    ProfileOpen(String),
     #[help(text="Quit the application")]
     Quit,
     #[help(text="Reload the current page")]
@@ -147,8 +163,35 @@ pub enum AppCommand {
     SearchPrevious,
     #[help(text="Select a user agent by name")]
     SelectUserAgent(String),
+    #[help(text="Delete a saved session")]
+    SessionDelete(String),
+    #[help(text="Open the windows of a saved session")]
+    SessionLoad(String),
+    #[help(text="Save the opened windows as a session")]
+    SessionSave(String),
+    #[completion(hidden)]
+    SetFor(String),
+    #[help(text="Run a program with the URL, title or selection of the page in its arguments")]
+    Spawn(String),
     #[help(text="Stop loading the current page")]
     Stop,
+    #[help(text="Close the current tab")]
+    TabClose,
+    #[help(text="Move the current tab to a position (N, +N or -N)")]
+    TabMove(String),
+    #[count]
+    #[help(text="Go to the next tab")]
+    TabNext(Option<u32>),
+    #[help(text="Open an URL in a new tab")]
+    TabOpen(String),
+    #[count]
+    #[help(text="Go to the previous tab")]
+    TabPrev(Option<u32>),
+    #[count]
+    #[help(text="Reopen the last closed windows")]
+    UndoClose(Option<u32>),
+    #[help(text="Reopen a recently closed window")]
+    UndoCloseSelect(String),
     #[completion(hidden)]
     UrlIncrement,
     #[completion(hidden)]
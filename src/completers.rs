@@ -20,16 +20,21 @@
  */
 
 use std::cmp::Ordering::{Greater, Less};
+use std::collections::HashSet;
 use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 
 use mg::completion::{Completer, CompletionCell, CompletionResult};
 use mg::completion::Column::{self, AllVisible, Expand};
 
-use app::USER_AGENT_COMPLETER;
+use app::{BUFFER_COMPLETER, UNDO_CLOSE_COMPLETER, USER_AGENT_COMPLETER};
 use app::user_agent::UserAgentManager;
-use bookmarks::{BookmarkInput, BookmarkManager};
+use bookmarks::{Bookmark, BookmarkInput, BookmarkManager};
+use config_dir::ConfigDir;
 use download::download_dir;
+use message_server::Privacy;
+use session;
+use windows::{ClosedWindows, OpenedWindows};
 
 /// A bookmark completer.
 pub struct BookmarkCompleter {
@@ -39,9 +44,9 @@ pub struct BookmarkCompleter {
 
 impl BookmarkCompleter {
     /// Create a new bookmark completer.
-    pub fn new(prefix: &'static str) -> Self {
+    pub fn new(prefix: &'static str, bookmarks: BookmarkManager) -> Self {
         BookmarkCompleter {
-            bookmarks: BookmarkManager::new(),
+            bookmarks,
             prefix: prefix,
         }
     }
@@ -82,25 +87,149 @@ impl Completer for BookmarkCompleter {
     }
 
     fn completions(&mut self, input: &str) -> Vec<CompletionResult> {
-        let mut results = vec![];
         let query = BookmarkCompleter::parse_input(input);
+        match self.bookmarks.query(query) {
+            Ok(bookmarks) => bookmarks.iter()
+                .map(bookmark_completion)
+                .collect(),
+            Err(error) => {
+                error!("Cannot query the bookmarks: {}", error);
+                vec![]
+            },
+        }
+    }
 
-        for bookmark in self.bookmarks.query(query) {
-            let tags =
-                if !bookmark.tags.is_empty() {
-                    format!("#{}", bookmark.tags)
-                }
-                else {
-                    String::new()
-                };
-            results.push(CompletionResult::from_cells(
-                &[&bookmark.title, &CompletionCell::new(&tags).foreground("#33DD00"), &bookmark.url],
-            ));
+    fn text_column(&self) -> i32 {
+        2
+    }
+}
+
+/// A completer for the pages from the browsing history and the bookmarks.
+/// The bookmarks are shown first.
+pub struct HistoryCompleter {
+    bookmark_completer: BookmarkCompleter,
+}
+
+impl HistoryCompleter {
+    /// Create a new history completer.
+    pub fn new(prefix: &'static str, bookmarks: BookmarkManager) -> Self {
+        HistoryCompleter {
+            bookmark_completer: BookmarkCompleter::new(prefix, bookmarks),
+        }
+    }
+}
+
+impl Completer for HistoryCompleter {
+    fn columns(&self) -> Vec<Column> {
+        self.bookmark_completer.columns()
+    }
+
+    fn complete_result(&self, value: &str) -> String {
+        self.bookmark_completer.complete_result(value)
+    }
+
+    fn completions(&mut self, input: &str) -> Vec<CompletionResult> {
+        let query = BookmarkCompleter::parse_input(input);
+        // Only the bookmarks have tags.
+        let search_history = query.tags.is_empty();
+        let words = query.words.clone();
+
+        let bookmarks = self.bookmark_completer.bookmarks.query(query)
+            .unwrap_or_else(|error| {
+                error!("Cannot query the bookmarks: {}", error);
+                vec![]
+            });
+        let mut results: Vec<_> = bookmarks.iter()
+            .map(bookmark_completion)
+            .collect();
+
+        if search_history {
+            let bookmarked_urls: HashSet<_> = bookmarks.iter()
+                .map(|bookmark| bookmark.url.as_str())
+                .collect();
+            match self.bookmark_completer.bookmarks.query_history(&words) {
+                Ok(entries) =>
+                    for entry in entries {
+                        if !bookmarked_urls.contains(entry.url.as_str()) {
+                            results.push(CompletionResult::new(&[&entry.title, &String::new(), &entry.url]));
+                        }
+                    },
+                Err(error) => error!("Cannot query the browsing history: {}", error),
+            }
         }
 
         results
     }
 
+    fn text_column(&self) -> i32 {
+        self.bookmark_completer.text_column()
+    }
+}
+
+/// A completer for the opened windows.
+pub struct BufferCompleter {
+    opened_windows: OpenedWindows,
+}
+
+impl BufferCompleter {
+    /// Create a new window completer.
+    pub fn new(opened_windows: OpenedWindows) -> Self {
+        BufferCompleter {
+            opened_windows,
+        }
+    }
+}
+
+impl Completer for BufferCompleter {
+    fn columns(&self) -> Vec<Column> {
+        vec![AllVisible, AllVisible, Expand, Expand]
+    }
+
+    fn complete_result(&self, value: &str) -> String {
+        format!("{} {}", BUFFER_COMPLETER, value)
+    }
+
+    fn completions(&mut self, input: &str) -> Vec<CompletionResult> {
+        let input = input.to_lowercase();
+        self.opened_windows.list().iter()
+            .filter(|window| window.title.to_lowercase().contains(&input) || window.url.to_lowercase().contains(&input))
+            .map(|window| CompletionResult::new(&[&window.id.to_string(), &privacy_flag(&window.privacy), &window.title,
+                &window.url]))
+            .collect()
+    }
+}
+
+/// A completer for the recently closed windows.
+pub struct ClosedWindowCompleter {
+    closed_windows: ClosedWindows,
+}
+
+impl ClosedWindowCompleter {
+    /// Create a new closed window completer.
+    pub fn new(closed_windows: ClosedWindows) -> Self {
+        ClosedWindowCompleter {
+            closed_windows,
+        }
+    }
+}
+
+impl Completer for ClosedWindowCompleter {
+    fn columns(&self) -> Vec<Column> {
+        vec![AllVisible, Expand, Expand]
+    }
+
+    fn complete_result(&self, value: &str) -> String {
+        format!("{} {}", UNDO_CLOSE_COMPLETER, value)
+    }
+
+    fn completions(&mut self, input: &str) -> Vec<CompletionResult> {
+        let input = input.to_lowercase();
+        self.closed_windows.list().iter()
+            .filter(|window| window.title.to_lowercase().contains(&input) || window.url.to_lowercase().contains(&input))
+            .map(|window| CompletionResult::new(&[&privacy_flag(&window.privacy), &window.title, &window.url]))
+            .collect()
+    }
+
     fn text_column(&self) -> i32 {
         2
     }
@@ -190,6 +319,39 @@ impl Completer for FileCompleter {
     }
 }
 
+/// A completer for the names of the saved sessions.
+pub struct SessionCompleter {
+    config_dir: ConfigDir,
+    prefix: &'static str,
+}
+
+impl SessionCompleter {
+    /// Create a new session completer.
+    pub fn new(prefix: &'static str, config_dir: ConfigDir) -> Self {
+        SessionCompleter {
+            config_dir,
+            prefix,
+        }
+    }
+}
+
+impl Completer for SessionCompleter {
+    fn columns(&self) -> Vec<Column> {
+        vec![Expand]
+    }
+
+    fn complete_result(&self, value: &str) -> String {
+        format!("{} {}", self.prefix, value)
+    }
+
+    fn completions(&mut self, input: &str) -> Vec<CompletionResult> {
+        session::list(&self.config_dir).iter()
+            .filter(|name| name.contains(input))
+            .map(|name| CompletionResult::new(&[name.as_str()]))
+            .collect()
+    }
+}
+
 /// A tag completer.
 pub struct TagCompleter {
     bookmarks: BookmarkManager,
@@ -197,9 +359,9 @@ pub struct TagCompleter {
 }
 
 impl TagCompleter {
-    pub fn new() -> Self {
+    pub fn new(bookmarks: BookmarkManager) -> Self {
         Self {
-            bookmarks: BookmarkManager::new(),
+            bookmarks,
             current_tags: String::new(),
         }
     }
@@ -225,13 +387,14 @@ impl Completer for TagCompleter {
             .unwrap_or(0);
         let query = input[index..].trim();
         self.current_tags = input[..index].to_string();
-        if let Ok(tags) = self.bookmarks.search_tags(query) {
-            tags.iter()
+        match self.bookmarks.search_tags(query) {
+            Ok(tags) => tags.iter()
                 .map(|tag| CompletionResult::new(&[&tag]))
-                .collect()
-        }
-        else {
-            vec![]
+                .collect(),
+            Err(error) => {
+                error!("Cannot search the tags: {}", error);
+                vec![]
+            },
         }
     }
 
@@ -274,6 +437,29 @@ impl Completer for UserAgentCompleter {
     }
 }
 
+/// Get the flag shown in the completions of the private and container windows.
+fn privacy_flag(privacy: &Privacy) -> String {
+    match *privacy {
+        Privacy::Normal => String::new(),
+        Privacy::Private => "[PV]".to_string(),
+        Privacy::Container(ref name) => format!("[{}]", name),
+    }
+}
+
+/// Create the completion result of a bookmark.
+fn bookmark_completion(bookmark: &Bookmark) -> CompletionResult {
+    let tags =
+        if !bookmark.tags.is_empty() {
+            format!("#{}", bookmark.tags)
+        }
+        else {
+            String::new()
+        };
+    CompletionResult::from_cells(
+        &[&bookmark.title, &CompletionCell::new(&tags).foreground("#33DD00"), &bookmark.url],
+    )
+}
+
 /// Split at whitespaces and at the # character.
 /// The # character will be kept in the words while the spaces are dropped.
 fn split_whitespace_and_hash(input: &str) -> Vec<String> {
@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Check of the configuration files without opening a window, for the `--check-config` option.
+
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::Path;
+
+use mg_settings::Command::{Custom, Map};
+use mg_settings::EnumFromStr;
+use url::Url;
+
+use commands::AppCommand::{self, SearchEngine};
+use config_file::{ConfigError, load_config};
+use stylesheet::check_document_header;
+
+/// The commands provided by mg, which can be used in the key mappings.
+/// mg does not export the list of its commands, so it must follow the mg version used: the check
+/// of the default configuration in the tests fails when a command used there is missing.
+const MG_COMMANDS: &[&str] = &[
+    "complete-next",
+    "complete-previous",
+    "entry",
+    "entry-copy",
+    "entry-cut",
+    "entry-delete-next-char",
+    "entry-delete-next-word",
+    "entry-delete-previous-word",
+    "entry-end",
+    "entry-next-char",
+    "entry-next-word",
+    "entry-paste",
+    "entry-paste-selection",
+    "entry-previous-char",
+    "entry-previous-word",
+    "entry-smart-home",
+];
+
+/// Check the config file and the files it includes, the key mappings, the search engines and the
+/// headers of the user style sheets in the configuration directory.
+pub fn check_config(config_home: &Path) -> Vec<ConfigError> {
+    let config = load_config(config_home);
+    let mut errors = config.errors;
+    for command in &config.commands {
+        let result =
+            match command.command {
+                Custom(SearchEngine(ref args)) => check_search_engine(args),
+                Map { ref action, .. } => check_mapping_action(action),
+                _ => Ok(()),
+            };
+        if let Err(message) = result {
+            errors.push(command.error(message));
+        }
+    }
+
+    let stylesheets_path = config_home.join("stylesheets");
+    if let Err(error) = check_stylesheets(&stylesheets_path, &mut errors) {
+        errors.push(ConfigError {
+            line: None,
+            message: error.to_string(),
+            path: stylesheets_path,
+        });
+    }
+    errors
+}
+
+/// Check that the action of a key mapping is an existing command with valid arguments.
+/// The actions starting with : only fill the command entry, so they are not checked.
+fn check_mapping_action(action: &str) -> Result<(), String> {
+    let action = action.trim();
+    if action.starts_with(':') {
+        return Ok(());
+    }
+    let (name, argument) =
+        match action.find(char::is_whitespace) {
+            Some(index) => (&action[..index], action[index..].trim()),
+            None => (action, ""),
+        };
+    if MG_COMMANDS.contains(&name) {
+        return Ok(());
+    }
+    AppCommand::create(name, argument, None)
+        .map(|_| ())
+        .map_err(|error| format!("invalid mapping action {}: {}", action, error))
+}
+
+/// Check the arguments of a search-engine command, like `search-engine keyword https://example.com/?q={}`.
+fn check_search_engine(args: &str) -> Result<(), String> {
+    let args: Vec<_> = args.split_whitespace().collect();
+    if args.len() != 2 {
+        return Err(format!("search-engine: expecting 2 arguments, got {} arguments", args.len()));
+    }
+    let url = args[1];
+    if !url.contains("{}") {
+        return Err(format!("search-engine: the URL {} does not contain {{}} for the search query", url));
+    }
+    Url::parse(&url.replace("{}", "query"))
+        .map(|_| ())
+        .map_err(|error| format!("search-engine: invalid URL {}: {}", url, error))
+}
+
+/// Check the @document header of the style sheets in the directory, if it exists.
+fn check_stylesheets(directory: &Path, errors: &mut Vec<ConfigError>) -> io::Result<()> {
+    if !directory.exists() {
+        return Ok(());
+    }
+    for entry in read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        match read_to_string(&path) {
+            Ok(content) =>
+                if let Err((line, message)) = check_document_header(&content) {
+                    errors.push(ConfigError {
+                        line: Some(line),
+                        message,
+                        path,
+                    });
+                },
+            Err(error) => errors.push(ConfigError {
+                line: None,
+                message: error.to_string(),
+                path,
+            }),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_mapping_action, check_search_engine};
+
+    #[test]
+    fn mapping_actions() {
+        assert!(check_mapping_action("scroll-down").is_ok());
+        assert!(check_mapping_action("entry-delete-next-word").is_ok());
+        assert!(check_mapping_action(":open ").is_ok());
+        assert!(check_mapping_action("scrol-down").is_err());
+    }
+
+    #[test]
+    fn search_engines() {
+        assert!(check_search_engine("wikipedia http://en.wikipedia.org/w/index.php?search={}").is_ok());
+        assert!(check_search_engine("wikipedia").is_err());
+        assert!(check_search_engine("wikipedia http://en.wikipedia.org/").is_err());
+        assert!(check_search_engine("wikipedia not-an-url/{}").is_err());
+    }
+}
@@ -33,27 +33,47 @@ enum ConfigDirOption {
     Xdg(BaseDirectories),
 }
 
+/// The directory, in the configuration directory, containing the named profiles.
+const PROFILE_DIR: &str = "profiles";
+
 /// Configuration directory manager.
 /// If `config_dir` is `None`, the XDG directory will be used.
+/// A named profile has its own configuration and data directories, in the `profiles` directory.
 #[derive(Clone)]
 pub struct ConfigDir {
+    config_dir: Option<String>,
     dir: ConfigDirOption,
+    profile: Option<String>,
 }
 
 impl ConfigDir {
-    pub fn new(config_dir: &Option<String>) -> Result<Self, BaseDirectoriesError> {
+    pub fn new(config_dir: &Option<String>, profile: &Option<String>) -> Result<Self, BaseDirectoriesError> {
         let dir =
-            if let Some(ref config_dir) = *config_dir {
-                Path(PathBuf::from(config_dir))
-            }
-            else {
-                Xdg(BaseDirectories::with_prefix(APP_NAME)?)
+            match (config_dir, profile) {
+                (&Some(ref config_dir), &Some(ref profile)) =>
+                    Path(PathBuf::from(config_dir).join(PROFILE_DIR).join(profile)),
+                (&Some(ref config_dir), &None) => Path(PathBuf::from(config_dir)),
+                (&None, &Some(ref profile)) =>
+                    Xdg(BaseDirectories::with_profile(APP_NAME, PathBuf::from(PROFILE_DIR).join(profile))?),
+                (&None, &None) => Xdg(BaseDirectories::with_prefix(APP_NAME)?),
             };
         Ok(ConfigDir {
+            config_dir: config_dir.clone(),
             dir: dir,
+            profile: profile.clone(),
         })
     }
 
+    /// Get the alternate configuration directory, if the XDG directory is not used.
+    pub fn custom_dir(&self) -> Option<&str> {
+        self.config_dir.as_ref().map(String::as_str)
+    }
+
+    /// Get the name of the profile, if it is not the default one.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_ref().map(String::as_str)
+    }
+
     /// Get a path to the config file.
     pub fn config_file(&self, filename: &str) -> io::Result<PathBuf> {
         match self.dir {
@@ -104,3 +124,9 @@ impl ConfigDir {
         }
     }
 }
+
+/// Check that the name of a profile or a container can be used as a directory name and in the GTK
+/// application ID.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|character| character.is_ascii_alphanumeric() || character == '-' || character == '_')
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Load the configuration files, reporting the errors with their file and line.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use mg_settings::{self, Command, Parser};
+use mg_settings::settings::Settings;
+
+use app::MODES;
+use commands::AppCommand;
+use settings::AppSettings;
+
+/// The maximum number of nested includes, to stop on include cycles.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// An error in a configuration file.
+pub struct ConfigError {
+    /// The line of the error, if it is not about the whole file.
+    pub line: Option<usize>,
+    pub message: String,
+    pub path: PathBuf,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(formatter, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(formatter, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// A command of a configuration file, with its position.
+pub struct ConfigCommand {
+    pub command: Command<AppCommand>,
+    pub line: usize,
+    pub path: PathBuf,
+}
+
+impl ConfigCommand {
+    /// Create an error at the position of the command.
+    pub fn error(&self, message: String) -> ConfigError {
+        ConfigError {
+            line: Some(self.line),
+            message,
+            path: self.path.clone(),
+        }
+    }
+}
+
+/// The commands of the configuration files, in order, with the errors found while parsing them.
+pub struct Config {
+    pub commands: Vec<ConfigCommand>,
+    pub errors: Vec<ConfigError>,
+}
+
+/// Parse the config file, in the configuration directory, and the files it includes.
+/// Unlike the parsing done when opening a window, a missing included file is an error.
+pub fn load_config(config_home: &Path) -> Config {
+    let mut config = Config {
+        commands: vec![],
+        errors: vec![],
+    };
+    let mut parser = Parser::new_with_config(mg_settings::Config {
+        application_commands: vec![],
+        mapping_modes: mapping_modes(),
+    });
+    let path = config_home.join("config");
+    if let Err(error) = load_file(&mut parser, config_home, &path, 0, &mut config) {
+        config.errors.push(ConfigError {
+            line: None,
+            message: error.to_string(),
+            path,
+        });
+    }
+    config
+}
+
+/// Parse a configuration file, adding its commands and errors to the config.
+fn load_file(parser: &mut Parser<AppCommand>, include_dir: &Path, path: &Path, depth: usize, config: &mut Config)
+    -> io::Result<()>
+{
+    let file = File::open(path)?;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let error = |message| ConfigError {
+            line: Some(index + 1),
+            message,
+            path: path.to_path_buf(),
+        };
+        if let Some(filename) = include_filename(&line) {
+            if depth == MAX_INCLUDE_DEPTH {
+                config.errors.push(error(format!("cannot include {}: too many nested includes", filename)));
+            }
+            else if let Err(include_error) = load_file(parser, include_dir, &include_dir.join(filename), depth + 1,
+                config)
+            {
+                config.errors.push(error(format!("cannot include {}: {}", filename, include_error)));
+            }
+            continue;
+        }
+
+        let result = parser.parse_line(&line, None);
+        config.errors.extend(result.errors.into_iter().map(|parse_error| error(parse_error.to_string())));
+        for command in result.commands {
+            if let Command::Set(ref name, ref value) = command {
+                if AppSettings::to_variant(name, value.clone()).is_err() {
+                    config.errors.push(error(format!("invalid setting {}", name)));
+                    continue;
+                }
+            }
+            config.commands.push(ConfigCommand {
+                command,
+                line: index + 1,
+                path: path.to_path_buf(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Get the file included by the line, if it is an include command.
+fn include_filename(line: &str) -> Option<&str> {
+    let mut words = line.trim().splitn(2, char::is_whitespace);
+    if words.next() == Some("include") {
+        words.next().map(str::trim)
+    }
+    else {
+        None
+    }
+}
+
+/// Get the prefixes of the modes available in the map commands.
+fn mapping_modes() -> Vec<&'static str> {
+    let mut modes = vec!["c", "n"];
+    modes.extend(MODES.iter().map(|mode| mode.prefix));
+    modes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use tempfile::Builder as TempFileBuilder;
+
+    use super::{include_filename, load_config};
+
+    #[test]
+    fn include_filenames() {
+        assert_eq!(include_filename("include keys"), Some("keys"));
+        assert_eq!(include_filename("  include  webkit "), Some("webkit"));
+        assert_eq!(include_filename("# include keys"), None);
+        assert_eq!(include_filename("set include = keys"), None);
+    }
+
+    #[test]
+    fn missing_include() {
+        let temp_dir = TempFileBuilder::new().tempdir().unwrap();
+        let mut file = File::create(temp_dir.path().join("config")).unwrap();
+        writeln!(file, "set hint-chars = abc").unwrap();
+        writeln!(file, "include missing").unwrap();
+
+        let config = load_config(temp_dir.path());
+        assert_eq!(config.commands.len(), 1);
+        assert_eq!(config.commands[0].line, 1);
+        assert_eq!(config.errors.len(), 1);
+        assert_eq!(config.errors[0].line, Some(2));
+        assert!(config.errors[0].message.starts_with("cannot include missing"));
+    }
+}
@@ -0,0 +1,528 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Unix domain socket to control the browser with a small JSON-RPC protocol.
+//!
+//! Every line sent on the socket is a JSON-RPC 2.0 request and every line received is either a
+//! response or, after a `subscribe` request, a notification.
+//!
+//! The methods are:
+//!
+//!  * `windows`: list the windows with their id, URL and title.
+//!  * `command`: run the `command` parameter like in the command entry.
+//!  * `selection`: get the text selected in the page.
+//!  * `wait-load`: answer with the URL when the page has finished loading.
+//!  * `subscribe`: receive the `url-changed` notifications, with the `window` id and the `url`.
+//!
+//! The methods acting on a window use the `window` parameter, or the active window.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, Permissions};
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use glib::{Continue, IOCondition, SourceId, unix_fd_add_local};
+use relm::StreamHandle;
+use serde_json::{self, Map, Value};
+
+/// The JSON-RPC error returned when a request is not valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC error returned when a request is not a valid request object.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The JSON-RPC error returned for an unknown method.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error returned when the parameters of a method are wrong.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The JSON-RPC error returned when a method fails.
+pub const SERVER_ERROR: i64 = -32000;
+
+/// The maximum size of a request and of the messages waiting to be sent to a client.
+/// The clients exceeding it are disconnected.
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The result of a request.
+pub type RpcResult<T> = Result<T, RpcError>;
+
+/// The queries answered by a window, asynchronously.
+#[derive(Clone)]
+pub enum ControlQuery {
+    /// Get the text selected in the page.
+    Selection,
+    /// Wait until the page is loaded.
+    WaitLoad,
+}
+
+/// A JSON-RPC error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: &str) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A request received from a client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub client: usize,
+    /// The id of the request, to be sent back in the response.
+    pub id: Value,
+    pub method: String,
+    pub params: Value,
+}
+
+impl Request {
+    /// Get a string parameter.
+    pub fn string_param(&self, name: &str) -> RpcResult<Option<String>> {
+        match self.params.get(name) {
+            None | Some(&Value::Null) => Ok(None),
+            Some(&Value::String(ref value)) => Ok(Some(value.clone())),
+            Some(_) => Err(RpcError::new(INVALID_PARAMS, &format!("{} must be a string", name))),
+        }
+    }
+
+    /// Get the window parameter.
+    pub fn window_param(&self) -> RpcResult<Option<usize>> {
+        match self.params.get("window") {
+            None | Some(&Value::Null) => Ok(None),
+            Some(id) => id.as_u64()
+                .map(|id| Some(id as usize))
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "window must be a window id")),
+        }
+    }
+}
+
+struct Client {
+    /// The bytes of the line being received.
+    buffer: Vec<u8>,
+    /// The bytes waiting to be sent, when the client does not read them fast enough.
+    output: Vec<u8>,
+    stream: UnixStream,
+    subscribed: bool,
+    /// The source sending the output when the socket becomes writable.
+    write_source: Option<SourceId>,
+}
+
+impl Client {
+    /// Close the connection.
+    /// The client is removed when its socket reports the hang up.
+    fn disconnect(&mut self) {
+        self.output.clear();
+        if let Err(error) = self.stream.shutdown(Shutdown::Both) {
+            warn!("Cannot disconnect the control client: {}", error);
+        }
+    }
+
+    /// Queue a message.
+    /// Returns false when the client does not read its messages.
+    fn queue(&mut self, message: &Value) -> bool {
+        self.output.extend_from_slice(format!("{}\n", message).as_bytes());
+        self.output.len() <= MAX_BUFFER_SIZE
+    }
+
+    /// Send the output that can be written without blocking.
+    /// Returns false when the connection is broken.
+    fn write_output(&mut self) -> bool {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return false,
+                Ok(size) => {
+                    self.output.drain(..size);
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => {
+                    warn!("Cannot write to control client: {}", error);
+                    return false;
+                },
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some(source) = self.write_source.take() {
+            source.remove();
+        }
+    }
+}
+
+struct Clients {
+    clients: HashMap<usize, Client>,
+    next_id: usize,
+}
+
+/// The control socket server.
+/// It is a handle that can be cloned to answer the requests.
+#[derive(Clone)]
+pub struct ControlServer {
+    clients: Rc<RefCell<Clients>>,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listen on the socket at the specified path.
+    /// The requests are sent, with their client id, to the stream as a message created by
+    /// `to_msg`.
+    pub fn start<MSG, F>(path: &Path, stream: StreamHandle<MSG>, to_msg: F) -> io::Result<Self>
+    where MSG: 'static,
+          F: Fn(Request) -> MSG + Clone + 'static,
+    {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)?;
+        // Only the user can control the browser.
+        fs::set_permissions(path, Permissions::from_mode(0o600))?;
+
+        let server = ControlServer {
+            clients: Rc::new(RefCell::new(Clients {
+                clients: HashMap::new(),
+                next_id: 0,
+            })),
+            path: path.to_path_buf(),
+        };
+        let accepting_server = server.clone();
+        unix_fd_add_local(listener.as_raw_fd(), IOCondition::IN, move |_, _| {
+            match listener.accept() {
+                Ok((client_stream, _)) => accepting_server.add_client(client_stream, stream.clone(), to_msg.clone()),
+                Err(error) => error!("Cannot accept a control client: {}", error),
+            }
+            Continue(true)
+        });
+        Ok(server)
+    }
+
+    fn add_client<MSG, F>(&self, client_stream: UnixStream, stream: StreamHandle<MSG>, to_msg: F)
+    where MSG: 'static,
+          F: Fn(Request) -> MSG + 'static,
+    {
+        // A client that does not read its messages must not block the browser.
+        if let Err(error) = client_stream.set_nonblocking(true) {
+            error!("Cannot accept a control client: {}", error);
+            return;
+        }
+        let fd = client_stream.as_raw_fd();
+        let id = {
+            let mut clients = self.clients.borrow_mut();
+            let id = clients.next_id;
+            clients.next_id += 1;
+            clients.clients.insert(id, Client {
+                buffer: vec![],
+                output: vec![],
+                stream: client_stream,
+                subscribed: false,
+                write_source: None,
+            });
+            id
+        };
+        let server = self.clone();
+        unix_fd_add_local(fd, IOCondition::IN | IOCondition::HUP | IOCondition::ERR, move |_, _| {
+            match server.read_lines(id) {
+                Some(lines) => {
+                    for line in lines {
+                        match parse_request(id, &line) {
+                            Ok(request) => stream.emit(to_msg(request)),
+                            Err((request_id, error)) => server.reply(id, request_id, Err(error)),
+                        }
+                    }
+                    Continue(true)
+                },
+                None => {
+                    server.clients.borrow_mut().clients.remove(&id);
+                    Continue(false)
+                },
+            }
+        });
+    }
+
+    /// Read the complete lines available from a client.
+    /// Returns None when the client is disconnected.
+    fn read_lines(&self, id: usize) -> Option<Vec<String>> {
+        let mut clients = self.clients.borrow_mut();
+        let client = clients.clients.get_mut(&id)?;
+        let mut bytes = [0; 4096];
+        let size =
+            match client.stream.read(&mut bytes) {
+                Ok(0) => return None,
+                Ok(size) => size,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted ||
+                    error.kind() == io::ErrorKind::WouldBlock => 0,
+                Err(error) => {
+                    warn!("Cannot read from control client: {}", error);
+                    return None;
+                },
+            };
+        client.buffer.extend_from_slice(&bytes[..size]);
+        let mut lines = vec![];
+        while let Some(index) = client.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<_> = client.buffer.drain(..=index).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        if client.buffer.len() > MAX_BUFFER_SIZE {
+            warn!("Disconnecting the control client: the request is too long");
+            return None;
+        }
+        Some(lines)
+    }
+
+    /// Send a notification to the clients that subscribed to the events.
+    pub fn notify(&self, method: &str, params: Value) {
+        let message = notification(method, params);
+        let mut clients = self.clients.borrow_mut();
+        for (&id, client) in clients.clients.iter_mut().filter(|&(_, ref client)| client.subscribed) {
+            self.send(id, client, &message);
+        }
+    }
+
+    /// Send the response to a request.
+    pub fn reply(&self, client: usize, id: Value, result: RpcResult<Value>) {
+        if let Some(client_data) = self.clients.borrow_mut().clients.get_mut(&client) {
+            self.send(client, client_data, &response(id, result));
+        }
+    }
+
+    /// Send a message without blocking.
+    /// What cannot be written yet is sent when the socket becomes writable, and the client is
+    /// disconnected when too many messages are waiting.
+    fn send(&self, id: usize, client: &mut Client, message: &Value) {
+        if !client.queue(message) {
+            warn!("Disconnecting the control client: it does not read its messages");
+            client.disconnect();
+        }
+        else if client.write_source.is_none() {
+            if !client.write_output() {
+                client.disconnect();
+            }
+            else if !client.output.is_empty() {
+                let server = self.clone();
+                client.write_source = Some(unix_fd_add_local(client.stream.as_raw_fd(), IOCondition::OUT,
+                    move |_, _| server.write_output(id)));
+            }
+        }
+    }
+
+    /// Send the output of a client when its socket becomes writable.
+    fn write_output(&self, id: usize) -> Continue {
+        if let Some(client) = self.clients.borrow_mut().clients.get_mut(&id) {
+            if !client.write_output() {
+                client.disconnect();
+            }
+            if !client.output.is_empty() {
+                return Continue(true);
+            }
+            // The source is removed by returning false.
+            client.write_source = None;
+        }
+        Continue(false)
+    }
+
+    /// Remove the socket.
+    pub fn stop(&self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Cannot remove the control socket {}: {}", self.path.display(), error);
+        }
+    }
+
+    /// Send the events to the client.
+    pub fn subscribe(&self, client: usize) {
+        if let Some(client) = self.clients.borrow_mut().clients.get_mut(&client) {
+            client.subscribed = true;
+        }
+    }
+}
+
+/// Remove the socket left by a previous process, but never another file.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is not a socket", path.display()))),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Parse a JSON-RPC request.
+/// When it is not valid, returns the id to use in the error response with the error.
+fn parse_request(client: usize, line: &str) -> Result<Request, (Value, RpcError)> {
+    let json: Value = serde_json::from_str(line)
+        .map_err(|error| (Value::Null, RpcError::new(PARSE_ERROR, &error.to_string())))?;
+    let id = json.get("id").cloned().unwrap_or(Value::Null);
+    let method =
+        match json.get("method") {
+            Some(&Value::String(ref method)) => method.clone(),
+            _ => return Err((id, RpcError::new(INVALID_REQUEST, "missing method"))),
+        };
+    let params =
+        match json.get("params") {
+            None => Value::Object(Map::new()),
+            Some(params @ &Value::Object(_)) => params.clone(),
+            Some(_) => return Err((id, RpcError::new(INVALID_PARAMS, "params must be an object"))),
+        };
+    Ok(Request {
+        client,
+        id,
+        method,
+        params,
+    })
+}
+
+/// Create a JSON-RPC response.
+fn response(id: Value, result: RpcResult<Value>) -> Value {
+    match result {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result,
+        }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": error.code,
+                "message": error.message,
+            },
+        }),
+    }
+}
+
+/// Create a JSON-RPC notification.
+fn notification(method: &str, params: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use serde_json::Value;
+    use tempfile::Builder as TempFileBuilder;
+
+    use super::{
+        Client,
+        INVALID_PARAMS,
+        INVALID_REQUEST,
+        PARSE_ERROR,
+        RpcError,
+        parse_request,
+        remove_stale_socket,
+        response,
+    };
+
+    #[test]
+    fn client_output() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut client = Client {
+            buffer: vec![],
+            output: vec![],
+            stream,
+            subscribed: false,
+            write_source: None,
+        };
+        assert!(client.queue(&json!(1)));
+        assert!(client.write_output());
+        assert!(client.output.is_empty());
+        let mut line = [0; 2];
+        peer.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"1\n");
+
+        // The messages are queued when the peer does not read them, until there are too many.
+        let message = Value::String("a".repeat(4096));
+        while client.queue(&message) {
+            assert!(client.write_output());
+        }
+        assert!(!client.output.is_empty());
+    }
+
+    #[test]
+    fn parse_requests() {
+        let request = parse_request(3, r#"{"jsonrpc": "2.0", "id": 1, "method": "command",
+            "params": {"command": "open example.com", "window": 2}}"#).unwrap();
+        assert_eq!(request.client, 3);
+        assert_eq!(request.id, json!(1));
+        assert_eq!(request.method, "command");
+        assert_eq!(request.string_param("command"), Ok(Some("open example.com".to_string())));
+        assert_eq!(request.window_param(), Ok(Some(2)));
+
+        let request = parse_request(0, r#"{"id": "a", "method": "windows"}"#).unwrap();
+        assert_eq!(request.id, json!("a"));
+        assert_eq!(request.window_param(), Ok(None));
+        assert_eq!(request.string_param("command"), Ok(None));
+    }
+
+    #[test]
+    fn parse_invalid_requests() {
+        assert_eq!(parse_request(0, "{").unwrap_err().1.code, PARSE_ERROR);
+        assert_eq!(parse_request(0, r#"{"id": 4}"#).unwrap_err(),
+            (json!(4), RpcError::new(INVALID_REQUEST, "missing method")));
+        assert_eq!(parse_request(0, r#"{"id": 5, "method": "windows", "params": [1]}"#).unwrap_err().1.code,
+            INVALID_PARAMS);
+
+        let request = parse_request(0, r#"{"id": 6, "method": "selection", "params": {"window": "1"}}"#).unwrap();
+        assert_eq!(request.window_param().unwrap_err().code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(response(json!(1), Ok(Value::Bool(true))).to_string(),
+            r#"{"id":1,"jsonrpc":"2.0","result":true}"#);
+        assert_eq!(response(Value::Null, Err(RpcError::new(PARSE_ERROR, "oops"))).to_string(),
+            r#"{"error":{"code":-32700,"message":"oops"},"id":null,"jsonrpc":"2.0"}"#);
+    }
+
+    #[test]
+    fn stale_sockets() {
+        let directory = TempFileBuilder::new().tempdir().unwrap();
+        let path = directory.path().join("control");
+        remove_stale_socket(&path).unwrap();
+
+        UnixListener::bind(&path).unwrap();
+        remove_stale_socket(&path).unwrap();
+        assert!(!path.exists());
+
+        File::create(&path).unwrap();
+        assert!(remove_stale_socket(&path).is_err());
+        assert!(path.exists());
+    }
+}
@@ -27,6 +27,7 @@ use std::str::Utf8Error;
 use glib;
 use password_store;
 use rusqlite;
+use serde_json;
 use zip::result::ZipError;
 
 pub struct Error {
@@ -61,6 +62,14 @@ impl<'a> From<&'a str> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error {
+            msg: error.to_string(),
+        }
+    }
+}
+
 impl From<glib::Error> for Error {
     fn from(error: glib::Error) -> Self {
         Error {
@@ -94,8 +94,6 @@
  *
  * FIXME: kill-win does not unfreeze the other web pages in the same web process.
  *
- * TODO: settings containing a list of websites that automatically open in private mode?
- *
  * FIXME: If nuon was opened by xdg-open (by e.g. zathura), the abstract domain socket can live
  * longer than what we want, preventing a new nuon from starting.
  * ~ ss -apx | rg nuon
@@ -121,8 +119,6 @@
  * TODO: exit insert mode after hitting Enter in text input.
  * TODO: default window size.
  *
- * FIXME: config file marks not created.
- *
  * TODO: save mark when focusing the first input.
  * TODO: save current scroll position in ' register before starting a search.
  *
@@ -184,7 +180,6 @@
  *
  * TODO: show the letters typed in follow mode.
  *
- * TODO: command to restore the last closed window.
  * TODO: command to open last deleted bookmark?
  *
  * TODO: rename the quit command to close.
@@ -193,11 +188,6 @@
  * TODO: automatically add new settings in the config files (kind of insertion sort, splitted by
  * the command? Detect whether a keymap was changed. What about those that were deleted?).
  *
- * TODO: should the private context be cleaned up when all the private windows are closed?
- * TODO: should there be a new private context every time the command private-win-open is issued?
- *
- * TODO: ResetMarks.
- *
  * TODO: hide hovered link when the text entry is shown? (Or show the hovered URL instead of the
  * current URL?)
  * TODO: bigger text entry than URL label.
@@ -267,9 +257,6 @@
  * FIXME: cannot scroll on https://translate.google.com/translate?hl=fr&sl=es&tl=en&u=http%3A%2F%2Fblog.bltavares.com%2F2017%2F01%2F18%2Fexpressando_o_dominio_atraves_do_sistema_de_tipos%2F (find the closest node which can scroll: if more than one are found at the same level, use the largest)
  * TODO: add tests for the scrolling element.
  *
- * TODO: cli argument for the abstract namespace of the unix domain socket.
- * TODO: cli argument for minimal log level.
- *
  * TODO: add a --redirect option or redirect command to bypass the adblocker.
  * TODO: add a command to do the redirections to avoid being blocked by the ad blocker.
  *
@@ -294,8 +281,7 @@
  * TODO: shortcut to (un)check all checkboxes in page (for email notification pages with many
  * checkboxes).
  *
- * TODO: sort bookmark completion with number of access (the most accessed URLs come first, then by
- * alphebetical order) and perhaps also by relevance (like the percentage of tags/words that
+ * TODO: also sort bookmark completion by relevance (like the percentage of tags/words that
  * matches).
  * TODO: automatically propose tags when editting bookmark tags (fetch them from the webpage, <meta
  * property="og:article:tag"/> is a start).
@@ -383,9 +369,11 @@
 extern crate cairo;
 extern crate gdk;
 extern crate gio;
+extern crate gio_sys;
 extern crate glib;
 extern crate gtk;
 extern crate gumdrop;
+extern crate libc;
 #[cfg(test)]
 extern crate libxdo;
 #[macro_use]
@@ -404,6 +392,11 @@ extern crate relm;
 #[macro_use]
 extern crate relm_derive;
 extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 extern crate simplelog;
 extern crate syslog;
 extern crate tempfile;
@@ -414,37 +407,62 @@ extern crate xdg;
 extern crate zip;
 
 mod app;
+mod bookmark_export;
+mod bookmark_import;
 mod bookmarks;
 mod clipboard;
 mod commands;
 mod completers;
+mod config_check;
 mod config_dir;
+mod config_file;
+mod control_server;
 mod download;
 mod download_view;
 mod download_list_view;
 mod errors;
 mod file;
 mod message_server;
+mod migrations;
 mod pass_manager;
 mod permission_manager;
 mod popup_manager;
+mod session;
 mod settings;
+mod site_privacy;
+mod site_settings;
 mod stylesheet;
 mod urls;
 mod webview;
+mod windows;
 
 use std::env::args;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use gio::{prelude::ApplicationExtManual, File, traits::{ApplicationExt, FileExt}};
-use gtk::Application;
+use gio::{
+    ApplicationCommandLine,
+    ApplicationFlags,
+    Cancellable,
+    File,
+    prelude::ApplicationExtManual,
+    traits::{ApplicationCommandLineExt, ApplicationExt, FileExt},
+};
+use glib::translate::ToGlibPtr;
+use gtk::{Application, traits::{GtkApplicationExt, GtkWindowExt}};
 use gumdrop::Options;
 use log::Level::Error;
-use relm::{EventStream, Relm, Update, UpdateNew, execute, Component};
+use relm::{EventStream, Relm, Update, UpdateNew, execute};
 use simplelog::{Config, LevelFilter, TermLogger};
 use syslog::Facility;
 
-use app::{App, APP_NAME};
-use message_server::{create_message_server, MessageServer, Msg::{NewApp, ReleaseApp}, Privacy};
+use app::APP_NAME;
+use app::remote::parse_command;
+use config_check::check_config;
+use config_dir::{ConfigDir, is_valid_name};
+use message_server::{create_message_server, MessageServer, Msg::{NewApp, ReleaseApp, RunCommand, StartControlServer}, Privacy};
+use urls::canonicalize_url_in;
 
 const INVALID_UTF8_ERROR: &str = "invalid utf-8 string";
 
@@ -457,44 +475,164 @@ pub const GTK_APP_NAME: &str = "com.nuon-browser.debug";
 
 #[derive(Debug, Default, Options)]
 struct Args {
+    #[options(no_short, help="Export the bookmarks to a Netscape HTML or JSON (.json) file and exit.", meta="PATH")]
+    bookmark_export: Option<String>,
+    #[options(no_short,
+        help="Check the configuration files, in the directory given as argument or the one of the profile, and exit.")]
+    check_config: bool,
     #[options(help="The configuration directory.")]
     config: Option<String>,
+    #[options(no_short, help="Listen to JSON-RPC requests on a Unix domain socket at this path.", meta="PATH")]
+    control_socket: Option<String>,
     #[options(help="Print help message.")]
     help: bool,
-    #[options(help="Show the log messages.")]
+    #[options(no_short, help="The name of the instance, to run several independent nuon processes.", meta="NAME")]
+    instance: Option<String>,
+    #[options(help="Show the log messages in the terminal instead of syslog.")]
     log: bool,
+    #[options(no_short, help="The minimal level of the log messages (off, error, warn, info, debug or trace).",
+        meta="LEVEL")]
+    log_level: Option<String>,
+    #[options(help="Open a new window even if nuon is already running.")]
+    new_window: bool,
+    #[options(help="Open the URLs in a private window.")]
+    private: bool,
+    #[options(no_short, help="The profile, which has its own configuration, bookmarks and cookies.", meta="NAME")]
+    profile: Option<String>,
+    #[options(no_short, help="Run a command, like open or bookmark, in the running instance.", meta="COMMAND")]
+    remote: Option<String>,
+    #[options(no_short, help="The id of the window in which the remote command runs (default: the active window).",
+        meta="ID")]
+    window: Option<usize>,
     #[options(free)]
     url: Vec<String>,
 }
 
 fn main() {
-    // TODO: switch to the GtkApplication API to handle cli arguments.
-    /*let args: Vec<_> = args().collect();
-
-    let args = match Args::parse_args_default(&args[1..]) {
-        Ok(options) => options,
-        Err(error) => {
-            println!("{}: {}", APP_NAME, error);
-            println!();
-            println!("{}", Args::usage());
-            return;
-        },
-    };
+    let arguments: Vec<_> = args().collect();
+    let options =
+        match Args::parse_args_default(&arguments[1..]) {
+            Ok(options) => options,
+            Err(error) => {
+                eprintln!("{}: {}", APP_NAME, error);
+                eprintln!();
+                eprintln!("{}", Args::usage());
+                process::exit(1);
+            },
+        };
 
-    if args.help {
-        println!("Usage: {} [OPTIONS] [ARGUMENTS]", APP_NAME);
+    if options.help {
+        println!("Usage: {} [OPTIONS] [URLS]", APP_NAME);
         println!();
         println!("{}", Args::usage());
+        return;
+    }
+
+    for name in options.profile.iter().chain(&options.instance) {
+        if !is_valid_name(name) {
+            eprintln!("{}: invalid name {}: only letters, digits, - and _ are allowed", APP_NAME, name);
+            process::exit(1);
+        }
+    }
+
+    let log_level =
+        match options.log_level {
+            Some(ref level) => match level.parse() {
+                Ok(level) => level,
+                Err(_) => {
+                    eprintln!("{}: invalid log level {}", APP_NAME, level);
+                    process::exit(1);
+                },
+            },
+            None => LevelFilter::max(),
+        };
+
+    // The bookmark export does not need the GUI, so handle it before starting the application.
+    if let Some(ref path) = options.bookmark_export {
+        match bookmark_export::export_from_config(&options.config, &options.profile, Path::new(path)) {
+            Ok(count) => println!("Exported {} bookmarks to {}", count, path),
+            Err(error) => {
+                eprintln!("{}: {}", APP_NAME, error);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+
+    // The config check does not need the GUI either.
+    if options.check_config {
+        process::exit(run_config_check(&options));
+    }
+
+    init_logging(options.log, log_level);
+
+    // The application is also activated without command line, for instance by D-Bus when it is
+    // started from a desktop file.
+    let flags = ApplicationFlags::HANDLES_COMMAND_LINE | ApplicationFlags::HANDLES_OPEN;
+    let application = Application::new(Some(&application_id(&options)), flags);
+
+    // A remote command is only sent to an instance that is already running.
+    if options.remote.is_some() {
+        if let Err(error) = application.register(None::<&Cancellable>) {
+            eprintln!("{}: {}", APP_NAME, error);
+            process::exit(1);
+        }
+        if !application.is_remote() {
+            eprintln!("{}: cannot run the remote command: nuon is not running", APP_NAME);
+            process::exit(1);
+        }
     }
-    else {*/
-        //init_logging(args.log);
 
-        let application = Application::new(Some(GTK_APP_NAME), gio::ApplicationFlags::HANDLES_OPEN);
+    let _app = execute::<RelmApp>((application.clone(), options.config.clone(), options.profile.clone()));
 
-        let _app = execute::<RelmApp>(application.clone());
+    // The arguments are parsed again in the command-line handler, which also receives those of the
+    // other nuon processes started while this one is running.
+    application.run_with_args(&arguments);
+}
 
-        application.run(/*&args*/);
-    //}
+/// Print the errors of the configuration files and get the exit code.
+fn run_config_check(options: &Args) -> i32 {
+    let config_home =
+        match options.url.as_slice() {
+            [] => match ConfigDir::new(&options.config, &options.profile) {
+                Ok(config_dir) => config_dir.config_home(),
+                Err(error) => {
+                    eprintln!("{}: {}", APP_NAME, error);
+                    return 1;
+                },
+            },
+            [directory] => PathBuf::from(directory),
+            _ => {
+                eprintln!("{}: --check-config expects at most one directory", APP_NAME);
+                return 1;
+            },
+        };
+    let errors = check_config(&config_home);
+    for error in &errors {
+        println!("{}", error);
+    }
+    if errors.is_empty() {
+        println!("{}: no errors found", config_home.display());
+        0
+    }
+    else {
+        eprintln!("{}: {} errors found", APP_NAME, errors.len());
+        1
+    }
+}
+
+/// Get the GTK application id.
+/// Every profile and instance is a different application, so that its windows are opened by its
+/// own process.
+fn application_id(options: &Args) -> String {
+    let mut application_id = GTK_APP_NAME.to_string();
+    if let Some(ref profile) = options.profile {
+        application_id.push_str(&format!(".profile-{}", profile));
+    }
+    if let Some(ref instance) = options.instance {
+        application_id.push_str(&format!(".instance-{}", instance));
+    }
+    application_id
 }
 
 struct RelmApp {
@@ -503,24 +641,30 @@ struct RelmApp {
 
 struct Model {
     application: Application,
+    config: Option<String>,
     message_server: Option<EventStream<<MessageServer as Update>::Msg>>,
+    profile: Option<String>,
 }
 
 #[derive(Msg)]
 enum Msg {
     Activate,
+    /// The arguments of a nuon process, with its command line.
+    CommandLine(Args, ApplicationCommandLine),
     Open(Vec<File>),
 }
 
 impl Update for RelmApp {
     type Model = Model;
-    type ModelParam = Application;
+    type ModelParam = (Application, Option<String>, Option<String>);
     type Msg = Msg;
 
-    fn model(_: &Relm<Self>, application: Self::ModelParam) -> Model {
+    fn model(_: &Relm<Self>, (application, config, profile): Self::ModelParam) -> Model {
         Model {
             application,
+            config,
             message_server: None,
+            profile,
         }
     }
 
@@ -537,42 +681,23 @@ impl Update for RelmApp {
             app.hold();
             Msg::Open(files.to_vec())
         });
+        connect!(relm, self.model.application, connect_command_line(app, command_line), return {
+            // NOTE: we need to increase the refcount of app because we create the window
+            // asynchronously.
+            app.hold();
+            (Some(command_line_msg(command_line)), 0)
+        });
     }
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::Activate => {
-                match self.model.message_server {
-                    Some(ref message_server) => {
-                        message_server.stream().emit(NewApp(None, Privacy::Normal));
-                        message_server.stream().emit(ReleaseApp);
-                    },
-                    None => {
-                        self.model.message_server = Some(create_message_server(self.model.application.clone(), vec![], None));
-                    },
-                }
-            },
+            Msg::Activate => self.open_urls(vec![], Privacy::Normal, false, None),
+            Msg::CommandLine(options, command_line) => self.command_line(options, command_line),
             Msg::Open(files) => {
-                match self.model.message_server {
-                    Some(ref message_server) => {
-                        if files.is_empty() {
-                            message_server.stream().emit(NewApp(None, Privacy::Normal));
-                            message_server.stream().emit(ReleaseApp);
-                        }
-                        else {
-                            for url in files {
-                                message_server.stream().emit(NewApp(Some(url.uri().to_string()), Privacy::Normal));
-                            }
-                            message_server.stream().emit(ReleaseApp);
-                        }
-                    },
-                    None => {
-                        let files = files.iter()
-                            .map(|file| file.uri().to_string())
-                            .collect();
-                        self.model.message_server = Some(create_message_server(self.model.application.clone(), files, None));
-                    },
-                }
+                let urls = files.iter()
+                    .map(|file| file.uri().to_string())
+                    .collect();
+                self.open_urls(urls, Privacy::Normal, false, None);
             },
         }
     }
@@ -586,7 +711,111 @@ impl UpdateNew for RelmApp {
     }
 }
 
-fn init_logging(log_to_term: bool) {
+impl RelmApp {
+    /// Open the windows or run the remote command requested on the command line.
+    /// When nuon is already running, the arguments of the new process are forwarded to this one.
+    /// The remote command errors are printed by the process which sent the command, which then exits
+    /// with a failure status.
+    fn command_line(&mut self, options: Args, command_line: ApplicationCommandLine) {
+        if let Some(command) = options.remote {
+            match (parse_command(&command), &self.model.message_server) {
+                (Err(error), _) => print_error(&command_line, &format!("invalid remote command {}", error)),
+                (Ok(_), Some(message_server)) =>
+                    message_server.stream().emit(RunCommand(options.window, command, command_line)),
+                (Ok(_), None) => print_error(&command_line, "cannot run the remote command: no window is opened"),
+            }
+            self.model.application.release();
+            return;
+        }
+
+        if self.model.message_server.is_some() {
+            // The options used to start the running process cannot be changed by a forwarded command line.
+            let error =
+                if options.config != self.model.config {
+                    Some("cannot use another config directory: nuon is already running with this profile")
+                }
+                else if options.control_socket.is_some() {
+                    Some("cannot start the control socket: nuon is already running with this profile")
+                }
+                else {
+                    None
+                };
+            if let Some(error) = error {
+                print_error(&command_line, error);
+                self.model.application.release();
+                return;
+            }
+        }
+
+        let directory = command_line.cwd();
+        let urls: Vec<_> = options.url.iter()
+            .map(|url| match directory {
+                Some(ref directory) => canonicalize_url_in(url, directory),
+                None => url.clone(),
+            })
+            .collect();
+        let privacy =
+            if options.private {
+                Privacy::Private
+            }
+            else {
+                Privacy::Normal
+            };
+        self.open_urls(urls, privacy, options.new_window, options.control_socket);
+    }
+
+    /// Open the URLs, or show the current window when there are none, unless a new or private
+    /// window is requested.
+    /// The control socket is only started with the first window.
+    fn open_urls(&mut self, urls: Vec<String>, privacy: Privacy, new_window: bool, control_socket: Option<String>) {
+        match self.model.message_server {
+            Some(ref message_server) => {
+                if urls.is_empty() {
+                    match self.model.application.active_window() {
+                        Some(ref window) if !new_window && privacy != Privacy::Private => window.present(),
+                        _ => message_server.stream().emit(NewApp(None, privacy.clone(), None)),
+                    }
+                }
+                for url in urls {
+                    message_server.stream().emit(NewApp(Some(url), privacy.clone(), None));
+                }
+                message_server.stream().emit(ReleaseApp);
+            },
+            None => {
+                let message_server = create_message_server(self.model.application.clone(), urls, privacy,
+                    self.model.config.clone(), self.model.profile.clone());
+                if let Some(path) = control_socket {
+                    message_server.stream().emit(StartControlServer(path));
+                }
+                self.model.message_server = Some(message_server);
+            },
+        }
+    }
+}
+
+/// Create the message for the arguments of a nuon process.
+fn command_line_msg(command_line: &ApplicationCommandLine) -> Msg {
+    let arguments: Vec<_> = command_line.arguments().into_iter()
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .collect();
+    // The arguments were already validated by the process that received them.
+    let options = Args::parse_args_default(arguments.get(1..).unwrap_or(&[])).unwrap_or_default();
+    Msg::CommandLine(options, command_line.clone())
+}
+
+/// Print an error in the terminal of the nuon process which sent the command line and make it exit
+/// with a failure status.
+pub fn print_error(command_line: &ApplicationCommandLine, message: &str) {
+    let message = CString::new(format!("{}: {}\n", APP_NAME, message)).unwrap_or_default();
+    // The variadic printerr function is not wrapped by gio.
+    unsafe {
+        gio_sys::g_application_command_line_printerr(command_line.to_glib_none().0, b"%s\0".as_ptr() as *const _,
+            message.as_ptr());
+    }
+    command_line.set_exit_status(1);
+}
+
+fn init_logging(log_to_term: bool, level: LevelFilter) {
     if log_to_term {
         let config = Config {
             time: Some(Error),
@@ -595,10 +824,10 @@ fn init_logging(log_to_term: bool) {
             location: None,
             time_format: None,
         };
-        TermLogger::init(LevelFilter::max(), config).unwrap();
+        TermLogger::init(level, config).unwrap();
     }
     else {
-        syslog::init_unix(Facility::LOG_USER, LevelFilter::max()).unwrap();
+        syslog::init_unix(Facility::LOG_USER, level).unwrap();
     }
     log_panics::init();
 }
@@ -21,17 +21,16 @@
 
 //! Message server interface.
 
-use std::collections::BTreeSet;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::io::{self, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::process;
 
-use gio::prelude::ApplicationExt;
+use gio::{self, ApplicationCommandLine, Cancellable, FileMonitor, FileMonitorEvent, FileMonitorFlags};
+use gio::prelude::{ApplicationExt, FileExt, FileMonitorExt};
 use gtk::Application;
 use gtk::traits::GtkApplicationExt;
 use gtk::{
-    traits::DialogExt,
+    traits::{DialogExt, WidgetExt},
     ButtonsType,
     DialogFlags,
     MessageDialog,
@@ -39,23 +38,55 @@ use gtk::{
     Window,
 };
 use relm::{Component, EventStream, Relm, Update, UpdateNew, execute, init};
+use serde_json::Value;
 use webkit2gtk::WebContext;
 
 use app::App;
+use app::remote::parse_command;
 use app::Msg::{
+    AnswerControlQuery,
+    ChangeTitle,
     ChangeUrl,
+    ConfigWatchChanged,
+    ControlReply,
+    CreatePrivateWindow,
     CreateWindow,
+    PrivateContextChanged,
+    ReloadAllConfigs,
+    ReloadConfig,
+    RemoteCommand,
     Remove,
+    RestoreWindow,
+    SessionStateChanged,
+    ShowError,
 };
+use bookmarks::BookmarkManager;
 use config_dir::ConfigDir;
+use control_server::{
+    ControlQuery,
+    ControlServer,
+    INVALID_PARAMS,
+    METHOD_NOT_FOUND,
+    Request,
+    RpcError,
+    RpcResult,
+    SERVER_ERROR,
+};
 use errors::Result;
+use print_error;
 use self::Msg::*;
+use session::{self, Session, SessionTab, SessionWindow};
+use settings::PrivateContext;
 use webview::WebView;
+use windows::{ClosedWindow, ClosedWindows, OpenedWindow, OpenedWindows};
 
-#[derive(Clone, Copy, PartialEq)]
+/// The web context in which a window is opened.
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
 pub enum Privacy {
     Normal,
     Private,
+    /// A named container, having its own cookies and website data.
+    Container(String),
 }
 
 pub struct MessageServer {
@@ -63,70 +94,139 @@ pub struct MessageServer {
 }
 
 pub struct Model {
-    app_count: usize,
     application: Application,
+    bookmark_manager: BookmarkManager,
+    /// The error that occurred when opening the bookmark database, to be shown in the next window.
+    bookmark_error: Option<String>,
+    closed_windows: ClosedWindows,
     config_dir: ConfigDir,
-    /// This listener is used to prevent two instances of nuon to run at the same time.
-    private_web_context: WebContext,
-    opened_urls: BTreeSet<String>,
-    previous_opened_urls: BTreeSet<String>,
+    /// The monitors of the configuration directories, when the config-watch setting is enabled.
+    config_monitors: Vec<FileMonitor>,
+    /// The web contexts of the containers, created when their first window is opened.
+    containers: HashMap<String, WebContext>,
+    control_server: Option<ControlServer>,
+    next_window_id: usize,
+    opened_windows: OpenedWindows,
+    previous_windows: Vec<SessionWindow>,
+    private_context: PrivateContext,
+    /// The web contexts of the opened private windows.
+    private_web_contexts: HashMap<usize, WebContext>,
+    /// The web context shared by the private windows, created when the first one is opened.
+    shared_private_web_context: Option<WebContext>,
     relm: Relm<MessageServer>,
-    // TODO: save the widgets somewhere allowing to remove them when its window is closed.
-    wins: Vec<Component<App>>,
+    wins: BTreeMap<usize, Component<App>>,
     web_context: WebContext,
 }
 
 #[derive(Msg)]
 pub enum Msg {
-    ChangeOpenedPage(String, String),
-    NewApp(Option<String>, Privacy),
+    ChangeOpenedPage(usize, String),
+    ChangeOpenedTitle(usize, String),
+    ChangeSessionState(usize, usize, Vec<SessionTab>),
+    ControlRequest(Request),
+    ControlResponse(usize, Value, RpcResult<Value>),
+    DestroyApp(usize),
+    /// Open a window, with the id of the window that opened it, if any.
+    NewApp(Option<String>, Privacy, Option<usize>),
+    /// Reload the configuration files in every window.
+    ReloadConfigs,
+    SetConfigWatch(bool),
+    SetPrivateContext(PrivateContext),
+    /// Listen to the control clients on the socket at the specified path.
+    StartControlServer(String),
     ReleaseApp,
-    RemoveApp(String),
+    RemoveApp(usize, ClosedWindow),
+    RestoreApp(SessionWindow),
+    /// Run a command in the window with the specified id, or in the active window.
+    /// The errors are reported to the nuon process which sent the command line.
+    RunCommand(Option<usize>, String, ApplicationCommandLine),
 }
 
 impl Update for MessageServer {
     type Model = Model;
-    type ModelParam = (Application, Vec<String>, Option<String>);
+    type ModelParam = (Application, Vec<String>, Privacy, Option<String>, Option<String>);
     type Msg = Msg;
 
-    fn model(relm: &Relm<Self>, (application, urls, config): (Application, Vec<String>, Option<String>)) -> Model {
-        let config_dir = ConfigDir::new(&config).unwrap(); // TODO: remove unwrap().
-        let (web_context, private_web_context) = WebView::initialize_web_extension(&config_dir);
+    fn model(relm: &Relm<Self>, (application, urls, privacy, config, profile): (Application, Vec<String>, Privacy,
+        Option<String>, Option<String>)) -> Model
+    {
+        let config_dir = ConfigDir::new(&config, &profile).unwrap(); // TODO: remove unwrap().
+        let web_context = WebView::initialize_web_extension(&config_dir);
+        let (bookmark_manager, bookmark_error) = open_bookmark_manager(&config_dir);
+        let previous_windows =
+            match session::load_recovery(&config_dir) {
+                Ok(session) => session.windows,
+                Err(error) => {
+                    error!("Load opened windows error: {}", error);
+                    vec![]
+                },
+            };
         if urls.is_empty() {
-            relm.stream().emit(NewApp(None, Privacy::Normal));
+            relm.stream().emit(NewApp(None, privacy, None));
             relm.stream().emit(ReleaseApp);
         }
         else {
             for url in urls {
-                relm.stream().emit(NewApp(Some(url), Privacy::Normal));
+                relm.stream().emit(NewApp(Some(url), privacy.clone(), None));
             }
             relm.stream().emit(ReleaseApp);
         }
         Model {
-            app_count: 0,
             application,
+            bookmark_error,
+            bookmark_manager,
+            closed_windows: ClosedWindows::new(),
             config_dir,
-            opened_urls: BTreeSet::new(),
-            previous_opened_urls: BTreeSet::new(),
-            private_web_context,
+            config_monitors: vec![],
+            containers: HashMap::new(),
+            control_server: None,
+            next_window_id: 0,
+            opened_windows: OpenedWindows::new(),
+            previous_windows,
+            private_context: PrivateContext::default(),
+            private_web_contexts: HashMap::new(),
             relm: relm.clone(),
-            wins: vec![],
+            shared_private_web_context: None,
+            wins: BTreeMap::new(),
             web_context,
         }
     }
 
     fn update(&mut self, event: Msg) {
         match event {
-            ChangeOpenedPage(old, new) => {
-                self.model.opened_urls.remove(&old);
-                self.model.opened_urls.insert(new);
-                self.save_urls();
+            ChangeOpenedPage(id, url) => {
+                self.notify_url_change(id, &url);
+                self.model.opened_windows.set_url(id, url);
+                self.save_windows();
+            },
+            ChangeOpenedTitle(id, title) => self.model.opened_windows.set_title(id, title),
+            ChangeSessionState(id, current_tab, tabs) => {
+                self.model.opened_windows.set_tabs(id, current_tab, tabs);
+                self.save_windows();
+            },
+            ControlRequest(request) => self.control_request(request),
+            ControlResponse(client, id, result) => self.control_reply(client, id, result),
+            DestroyApp(id) => {
+                self.model.wins.remove(&id);
             },
-            NewApp(url, privacy) => self.add_app(url, privacy),
+            NewApp(url, privacy, opener) => self.add_app(url, privacy, None, opener),
             // NOTE: we called hold() on the application in order to create the window
             // asynchronously. Now that it is created, we can call release().
             ReleaseApp => self.model.application.release(),
-            RemoveApp(url) => self.remove_app(url),
+            ReloadConfigs => {
+                for app in self.model.wins.values() {
+                    app.emit(ReloadConfig);
+                }
+            },
+            RemoveApp(id, window) => self.remove_app(id, window),
+            RestoreApp(window) => {
+                let url = window.url().to_string();
+                self.add_app(Some(url), window.privacy.clone(), Some(window), None);
+            },
+            RunCommand(id, command, command_line) => self.run_command(id, command, &command_line),
+            SetConfigWatch(watch) => self.set_config_watch(watch),
+            SetPrivateContext(private_context) => self.model.private_context = private_context,
+            StartControlServer(path) => self.start_control_server(&path),
         }
     }
 }
@@ -140,86 +240,282 @@ impl UpdateNew for MessageServer {
 }
 
 impl MessageServer {
-    pub fn new(application: Application, url: Vec<String>, config_dir: Option<String>) -> Result<EventStream<<Self as Update>::Msg>> {
-        Ok(execute::<MessageServer>((application, url, config_dir)))
+    pub fn new(application: Application, url: Vec<String>, privacy: Privacy, config_dir: Option<String>,
+        profile: Option<String>) -> Result<EventStream<<Self as Update>::Msg>>
+    {
+        Ok(execute::<MessageServer>((application, url, privacy, config_dir, profile)))
     }
 
-    fn add_app(&mut self, url: Option<String>, privacy: Privacy) {
-        self.model.app_count += 1;
+    fn add_app(&mut self, url: Option<String>, privacy: Privacy, window: Option<SessionWindow>, opener: Option<usize>) {
+        let id = self.model.next_window_id;
+        self.model.next_window_id += 1;
         let web_context =
-            if privacy == Privacy::Private {
-                self.model.private_web_context.clone()
-            }
-            else {
-                self.model.web_context.clone()
+            match privacy {
+                Privacy::Normal => self.model.web_context.clone(),
+                Privacy::Private => {
+                    let web_context = self.private_web_context(opener);
+                    self.model.private_web_contexts.insert(id, web_context.clone());
+                    web_context
+                },
+                Privacy::Container(ref name) => {
+                    let config_dir = &self.model.config_dir;
+                    self.model.containers.entry(name.clone())
+                        .or_insert_with(|| WebView::container_context(config_dir, name))
+                        .clone()
+                },
             };
 
-        self.load_opened_urls();
+        let (current_tab, tabs) =
+            match window {
+                Some(ref window) => (window.current_tab, window.tabs.clone()),
+                None => (0, url.iter().map(|url| SessionTab { state: None, url: url.clone() }).collect()),
+            };
+        let app = init::<App>((url.clone(), window, self.model.config_dir.clone(), web_context, privacy.clone(),
+            self.model.bookmark_manager.clone(), self.model.previous_windows.clone(),
+            self.model.closed_windows.clone(), self.model.opened_windows.clone()))
+            .unwrap(); // TODO: remove unwrap().
+        if let Some(error) = self.model.bookmark_error.take() {
+            app.emit(ShowError(error));
+        }
+        self.model.application.add_window(app.widget());
+        self.model.opened_windows.insert(OpenedWindow {
+            current_tab,
+            id,
+            privacy,
+            tabs,
+            title: String::new(),
+            url: url.unwrap_or_default(),
+            window: app.widget().clone(),
+        });
+        self.save_windows();
+        connect!(app@CreateWindow(ref url, ref privacy), self.model.relm,
+            NewApp(Some(url.clone()), privacy.clone(), Some(id)));
+        connect!(app@CreatePrivateWindow(ref url), self.model.relm, NewApp(Some(url.clone()), Privacy::Private, None));
+        connect!(app@PrivateContextChanged(ref private_context), self.model.relm,
+            SetPrivateContext(private_context.clone()));
+        connect!(app@ConfigWatchChanged(ref watch), self.model.relm, SetConfigWatch(*watch));
+        connect!(app@ReloadAllConfigs, self.model.relm, ReloadConfigs);
+        connect!(app@Remove(ref window), self.model.relm, RemoveApp(id, window.clone()));
+        connect!(app@ChangeTitle(ref title), self.model.relm, ChangeOpenedTitle(id, title.clone()));
+        connect!(app@ControlReply(ref client, ref request_id, ref result), self.model.relm,
+            ControlResponse(*client, request_id.clone(), result.clone()));
+        connect!(app@ChangeUrl(_, ref new), self.model.relm, ChangeOpenedPage(id, new.clone()));
+        connect!(app@RestoreWindow(ref window), self.model.relm, RestoreApp(window.clone()));
+        connect!(app@SessionStateChanged(ref current_tab, ref tabs), self.model.relm,
+            ChangeSessionState(id, *current_tab, tabs.clone()));
+        connect!(app.widget(), connect_destroy(_), self.model.relm, DestroyApp(id));
+        self.model.wins.insert(id, app);
+    }
 
-        if let Some(url) = url.as_ref() {
-            self.model.opened_urls.insert(url.clone());
-            self.save_urls();
+    /// Get the web context of a new private window.
+    /// Without a shared context, the window uses the context of the private window that opened it,
+    /// or a new one.
+    fn private_web_context(&mut self, opener: Option<usize>) -> WebContext {
+        match self.model.private_context {
+            PrivateContext::Shared =>
+                self.model.shared_private_web_context.get_or_insert_with(WebView::private_context).clone(),
+            PrivateContext::PerWindow =>
+                opener.and_then(|id| self.model.private_web_contexts.get(&id).cloned())
+                    .unwrap_or_else(WebView::private_context),
         }
+    }
 
-        let app = init::<App>((url, self.model.config_dir.clone(), web_context, self.model.previous_opened_urls.clone())).unwrap(); // TODO: remove unwrap().
-        self.model.application.add_window(app.widget());
-        connect!(app@CreateWindow(ref url, ref privacy), self.model.relm, NewApp(Some(url.clone()), *privacy));
-        connect!(app@Remove(ref url), self.model.relm, RemoveApp(url.clone()));
-        connect!(app@ChangeUrl(ref old, ref new), self.model.relm, ChangeOpenedPage(old.clone(), new.clone()));
-        self.model.wins.push(app);
-    }
-
-    fn load_opened_urls(&mut self) {
-        let mut restore = || -> io::Result<()> {
-            let filename = self.model.config_dir.data_file("urls")?;
-            let file = BufReader::new(File::open(filename)?);
-            for line in file.lines() {
-                let url = line?;
-                self.model.opened_urls.insert(url.clone());
-                self.model.previous_opened_urls.insert(url.clone());
-            }
+    fn remove_app(&mut self, id: usize, window: ClosedWindow) {
+        self.model.opened_windows.remove(id);
+        self.save_windows();
+        self.remove_private_web_context(id);
 
-            Ok(())
-        };
+        if !window.url.is_empty() {
+            self.model.closed_windows.push(window);
+        }
+    }
 
-        if let Err(error) = restore() {
-            error!("Load opened urls error: {}", error);
+    /// Forget the web context of a closed private window.
+    /// The private website data is deleted when the last window using it is closed.
+    fn remove_private_web_context(&mut self, id: usize) {
+        if let Some(web_context) = self.model.private_web_contexts.remove(&id) {
+            if !self.model.private_web_contexts.values().any(|context| *context == web_context) {
+                WebView::clear_website_data(&web_context);
+                if self.model.shared_private_web_context.as_ref() == Some(&web_context) {
+                    self.model.shared_private_web_context = None;
+                }
+            }
         }
     }
 
-    fn remove_app(&mut self, url: String) {
-        self.model.opened_urls.remove(&url);
-        self.save_urls();
+    /// Get the window with the specified id, or the active window.
+    fn find_app(&self, id: Option<usize>) -> Option<&Component<App>> {
+        match id {
+            Some(id) => self.model.wins.get(&id),
+            None => self.model.application.active_window()
+                .and_then(|window| self.model.wins.values().find(|app| *app.widget() == window)),
+        }
+    }
 
-        self.model.app_count -= 1;
-        // TODO: remove from self.model.wins.
-        if self.model.app_count == 0 {
-            self.model.opened_urls.clear();
-            self.save_urls();
+    /// Run a command sent by another nuon process.
+    fn run_command(&self, id: Option<usize>, command: String, command_line: &ApplicationCommandLine) {
+        match self.find_app(id) {
+            Some(app) => app.emit(RemoteCommand(command)),
+            None => print_error(command_line, &format!("cannot run the remote command {}: no such window", command)),
         }
     }
 
-    fn save_urls(&self) {
-        let save = || -> io::Result<()> {
-            let filename = self.model.config_dir.data_file("urls")?;
-            let mut file = File::create(filename)?;
-            for url in &self.model.opened_urls {
-                writeln!(file, "{}", url)?;
+    /// Start or stop reloading the configuration in every window when the configuration files change.
+    fn set_config_watch(&mut self, watch: bool) {
+        if !watch {
+            for monitor in self.model.config_monitors.drain(..) {
+                monitor.cancel();
             }
+            return;
+        }
+        if !self.model.config_monitors.is_empty() {
+            return;
+        }
+
+        let config_home = self.model.config_dir.config_home();
+        for path in &[config_home.clone(), config_home.join("scripts"), config_home.join("stylesheets")] {
+            let directory = gio::File::for_path(path);
+            match directory.monitor_directory(FileMonitorFlags::WATCH_MOVES, None::<&Cancellable>) {
+                Ok(monitor) => {
+                    connect!(self.model.relm, monitor, connect_changed(_, file, _, event),
+                        config_change_msg(file, event));
+                    self.model.config_monitors.push(monitor);
+                },
+                Err(error) => error!("Cannot watch the configuration directory {}: {}", path.display(), error),
+            }
+        }
+    }
+
+    fn start_control_server(&mut self, path: &str) {
+        match ControlServer::start(Path::new(path), self.model.relm.stream().clone(), ControlRequest) {
+            Ok(control_server) => {
+                let server = control_server.clone();
+                self.model.application.connect_shutdown(move |_| server.stop());
+                self.model.control_server = Some(control_server);
+            },
+            Err(error) => error!("Cannot start the control server on {}: {}", path, error),
+        }
+    }
 
-            Ok(())
-        };
+    /// Handle a request of a control client.
+    /// The requests needing the web view are answered later by the window.
+    fn control_request(&mut self, request: Request) {
+        let result =
+            match request.method.as_str() {
+                "command" => self.control_command(&request).map(|()| Some(Value::Null)),
+                "selection" => self.control_query(&request, ControlQuery::Selection).map(|()| None),
+                "subscribe" => {
+                    if let Some(ref control_server) = self.model.control_server {
+                        control_server.subscribe(request.client);
+                    }
+                    Ok(Some(Value::Bool(true)))
+                },
+                "wait-load" => self.control_query(&request, ControlQuery::WaitLoad).map(|()| None),
+                "windows" => Ok(Some(self.windows_json())),
+                method => Err(RpcError::new(METHOD_NOT_FOUND, &format!("unknown method {}", method))),
+            };
+        match result {
+            Ok(Some(result)) => self.control_reply(request.client, request.id, Ok(result)),
+            Ok(None) => (),
+            Err(error) => self.control_reply(request.client, request.id, Err(error)),
+        }
+    }
 
-        if let Err(error) = save() {
-            error!("Cannot save opened urls: {}", error);
+    fn control_reply(&self, client: usize, id: Value, result: RpcResult<Value>) {
+        if let Some(ref control_server) = self.model.control_server {
+            control_server.reply(client, id, result);
+        }
+    }
+
+    fn control_app(&self, request: &Request) -> RpcResult<&Component<App>> {
+        let id = request.window_param()?;
+        self.find_app(id)
+            .ok_or_else(|| RpcError::new(SERVER_ERROR, "no such window"))
+    }
+
+    /// Run a command in a window, like the commands typed in the command entry.
+    fn control_command(&self, request: &Request) -> RpcResult<()> {
+        let command = request.string_param("command")?
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing command"))?;
+        parse_command(&command)
+            .map_err(|error| RpcError::new(INVALID_PARAMS, &format!("invalid command {}", error)))?;
+        self.control_app(request)?.emit(RemoteCommand(command));
+        Ok(())
+    }
+
+    fn control_query(&self, request: &Request, query: ControlQuery) -> RpcResult<()> {
+        self.control_app(request)?.emit(AnswerControlQuery(request.client, request.id.clone(), query));
+        Ok(())
+    }
+
+    /// Get the opened windows, with their id, URL and title.
+    fn windows_json(&self) -> Value {
+        let active_window = self.model.application.active_window();
+        let windows = self.model.opened_windows.list().into_iter()
+            .map(|window| {
+                let container =
+                    match window.privacy {
+                        Privacy::Container(ref name) => Value::String(name.clone()),
+                        Privacy::Normal | Privacy::Private => Value::Null,
+                    };
+                json!({
+                    "active": Some(&window.window) == active_window.as_ref(),
+                    "container": container,
+                    "id": window.id,
+                    "private": window.privacy == Privacy::Private,
+                    "title": window.title,
+                    "url": window.url,
+                })
+            })
+            .collect();
+        Value::Array(windows)
+    }
+
+    /// Send the new URL of a window to the control clients.
+    fn notify_url_change(&self, id: usize, url: &str) {
+        if let Some(ref control_server) = self.model.control_server {
+            control_server.notify("url-changed", json!({
+                "url": url,
+                "window": id,
+            }));
+        }
+    }
+
+    /// Save the opened windows to restore them after a crash.
+    /// The private windows are not saved.
+    fn save_windows(&self) {
+        let windows = self.model.opened_windows.list().into_iter()
+            .filter(|window| window.privacy != Privacy::Private)
+            .collect();
+        if let Err(error) = session::save_recovery(&self.model.config_dir, &Session::new(windows)) {
+            error!("Cannot save opened windows: {}", error);
         }
     }
 }
 
+/// Open the bookmark database shared by all the windows.
+/// If it cannot be opened, a temporary in-memory database is used instead and the error is
+/// returned. The windows then show in their status bar that the bookmarks are not saved.
+fn open_bookmark_manager(config_dir: &ConfigDir) -> (BookmarkManager, Option<String>) {
+    let result = App::bookmark_path(config_dir)
+        .and_then(|path| BookmarkManager::open(&path));
+    match result {
+        Ok(bookmark_manager) => (bookmark_manager, None),
+        Err(error) => {
+            error!("Cannot open the bookmark database: {}", error);
+            let bookmark_manager = BookmarkManager::open(Path::new(":memory:"))
+                .expect("cannot create an in-memory bookmark database");
+            (bookmark_manager, Some(error.to_string()))
+        },
+    }
+}
+
 /// Create a new message server.
 /// If it is not possible to create one, show the error and exit.
-pub fn create_message_server(application: Application, urls: Vec<String>, config_dir: Option<String>) -> EventStream<<MessageServer as Update>::Msg> {
-    match MessageServer::new(application, urls, config_dir) {
+pub fn create_message_server(application: Application, urls: Vec<String>, privacy: Privacy,
+    config_dir: Option<String>, profile: Option<String>) -> EventStream<<MessageServer as Update>::Msg>
+{
+    match MessageServer::new(application, urls, privacy, config_dir, profile) {
         Ok(message_server) => message_server,
         Err(error) => {
             let message = format!("cannot create the message server used to communicate with the web processes: {}",
@@ -236,3 +532,16 @@ fn dialog_and_exit(message: &str) -> ! {
     dialog.run();
     process::exit(1);
 }
+
+/// Get the message to reload the configuration after a change in a configuration directory.
+/// The bookmark database, stored in the same directory, is ignored.
+fn config_change_msg(file: &gio::File, event: FileMonitorEvent) -> Option<Msg> {
+    let is_database = file.basename()
+        .and_then(|name| name.to_str().map(|name| name.starts_with("bookmarks.db")))
+        .unwrap_or(false);
+    match event {
+        FileMonitorEvent::ChangesDoneHint | FileMonitorEvent::Deleted | FileMonitorEvent::MovedIn |
+            FileMonitorEvent::Renamed if !is_database => Some(ReloadConfigs),
+        _ => None,
+    }
+}
@@ -0,0 +1,347 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Versioned schema migrations of the nuon database.
+//!
+//! The schema version is stored in `PRAGMA user_version`: a database at version `n` has all the
+//! `n` first migrations applied.
+//! The databases created before the migrations were introduced are at version 0 even though they
+//! can contain some of the tables, so the first migrations must work on those too.
+
+use std::fs::{copy, metadata, remove_file};
+use std::path::{Path, PathBuf};
+use std::result;
+
+use rusqlite::Connection;
+
+use errors::{Error, Result};
+
+/// A migration upgrades the schema to the next version.
+type Migration = fn(&Connection) -> Result<()>;
+
+/// The migrations, in the order they must be applied.
+/// New migrations are added at the end: never modify or reorder the existing ones.
+const MIGRATIONS: &[Migration] = &[
+    create_bookmark_tables,
+    create_history_tables,
+    create_mark_tables,
+    create_full_text_indexes,
+];
+
+/// Get the schema version that the migrations bring the database to.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+/// Apply the pending migrations to the database at `path`.
+/// Every migration is applied in its own transaction.
+/// The database file is backed up before the upgrade, and the backup is kept if a migration
+/// fails.
+pub fn migrate(connection: &Connection, path: &Path) -> Result<()> {
+    let version = schema_version(connection)?;
+    if version > latest_version() {
+        return Err(Error::from_string(format!(
+            "The database {} has the schema version {} which is newer than the supported version {}",
+            path.display(), version, latest_version())));
+    }
+    if version == latest_version() {
+        return Ok(());
+    }
+
+    let backup_path = backup(path, version)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let new_version = index as u32 + 1;
+        let transaction = connection.unchecked_transaction()?;
+        let result = migration(connection)
+            .and_then(|()| {
+                connection.pragma_update(None, "user_version", &new_version)?;
+                Ok(())
+            });
+        if let Err(error) = result {
+            drop(transaction);
+            let backup_message = backup_path.as_ref()
+                .map(|backup_path| format!(" (backup saved to {})", backup_path.display()))
+                .unwrap_or_default();
+            return Err(Error::from_string(format!("Cannot upgrade the database {} to version {}: {}{}",
+                path.display(), new_version, error, backup_message)));
+        }
+        transaction.commit()?;
+    }
+
+    if let Some(backup_path) = backup_path {
+        remove_file(backup_path)?;
+    }
+    Ok(())
+}
+
+/// Get the schema version of the database.
+pub fn schema_version(connection: &Connection) -> Result<u32> {
+    Ok(connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Copy the database file next to it, unless it does not exist yet.
+/// Returns the path of the backup.
+fn backup(path: &Path, version: u32) -> Result<Option<PathBuf>> {
+    let is_empty = metadata(path)
+        .map(|metadata| metadata.len() == 0)
+        .unwrap_or(true);
+    if is_empty {
+        return Ok(None);
+    }
+    let mut backup_path = path.as_os_str().to_os_string();
+    backup_path.push(format!(".v{}.bak", version));
+    let backup_path = PathBuf::from(backup_path);
+    copy(path, &backup_path)?;
+    Ok(Some(backup_path))
+}
+
+/// Version 1: the bookmarks and their tags.
+fn create_bookmark_tables(connection: &Connection) -> Result<()> {
+    connection.execute_batch("
+        CREATE TABLE IF NOT EXISTS bookmarks
+        ( id INTEGER PRIMARY KEY
+        , title TEXT NOT NULL
+        , url TEXT NOT NULL UNIQUE
+        , visit_count INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS tags
+        ( id INTEGER PRIMARY KEY
+        , name TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS bookmarks_tags
+        ( bookmark_id INTEGER NOT NULL
+        , tag_id INTEGER NOT NULL
+        , PRIMARY KEY (bookmark_id, tag_id)
+        , FOREIGN KEY(bookmark_id) REFERENCES bookmarks(id) ON DELETE CASCADE
+        , FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        );
+    ")?;
+    Ok(())
+}
+
+/// Version 2: the browsing history and the date of the last visit of the bookmarks.
+fn create_history_tables(connection: &Connection) -> Result<()> {
+    if !has_column(connection, "bookmarks", "last_visit")? {
+        connection.execute("ALTER TABLE bookmarks ADD COLUMN last_visit INTEGER", [])?;
+    }
+
+    connection.execute_batch("
+        CREATE TABLE IF NOT EXISTS history
+        ( id INTEGER PRIMARY KEY
+        , title TEXT NOT NULL
+        , url TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS history_visits
+        ( history_id INTEGER NOT NULL
+        , visited_at INTEGER NOT NULL
+        , FOREIGN KEY(history_id) REFERENCES history(id) ON DELETE CASCADE
+        );
+    ")?;
+    Ok(())
+}
+
+/// Version 3: the marks of the pages and the global marks.
+fn create_mark_tables(connection: &Connection) -> Result<()> {
+    connection.execute_batch("
+        CREATE TABLE IF NOT EXISTS marks
+        ( url TEXT NOT NULL
+        , mark INTEGER NOT NULL
+        , anchor TEXT
+        , anchor_offset INTEGER NOT NULL
+        , percentage INTEGER NOT NULL
+        , PRIMARY KEY (url, mark)
+        );
+
+        CREATE TABLE IF NOT EXISTS global_marks
+        ( mark INTEGER PRIMARY KEY
+        , url TEXT NOT NULL
+        , anchor TEXT
+        , anchor_offset INTEGER NOT NULL
+        , percentage INTEGER NOT NULL
+        );
+    ")?;
+    // The first mark tables only had the scroll percentage.
+    add_anchor_columns(connection, "marks")?;
+    add_anchor_columns(connection, "global_marks")
+}
+
+/// Add the columns of the element a mark is anchored to, if the table does not have them.
+fn add_anchor_columns(connection: &Connection, table: &str) -> Result<()> {
+    if !has_column(connection, table, "anchor")? {
+        connection.execute(&format!("ALTER TABLE {} ADD COLUMN anchor TEXT", table), [])?;
+    }
+    if !has_column(connection, table, "anchor_offset")? {
+        connection.execute(&format!("ALTER TABLE {} ADD COLUMN anchor_offset INTEGER NOT NULL DEFAULT 0", table),
+            [])?;
+    }
+    Ok(())
+}
+
+/// Version 4: the description of the bookmarks and the full-text indexes of the bookmarks and the
+/// history.
+fn create_full_text_indexes(connection: &Connection) -> Result<()> {
+    if !has_column(connection, "bookmarks", "description")? {
+        connection.execute("ALTER TABLE bookmarks ADD COLUMN description TEXT NOT NULL DEFAULT ''", [])?;
+    }
+    create_full_text_index(connection, "bookmarks", &["title", "url", "description"])?;
+    create_full_text_index(connection, "history", &["title", "url"])
+}
+
+/// Create the full-text index of the columns of a table, kept in sync with it by triggers.
+/// The index is named after the table, with the `_fts` suffix.
+fn create_full_text_index(connection: &Connection, table: &str, columns: &[&str]) -> Result<()> {
+    let index = format!("{}_fts", table);
+    let column_list = columns.join(", ");
+    let new_values = columns.iter()
+        .map(|column| format!("new.{}", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let old_values = columns.iter()
+        .map(|column| format!("old.{}", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if !has_table(connection, &index)? {
+        connection.execute_batch(&format!("
+            CREATE VIRTUAL TABLE {index} USING fts5({columns}, content='{table}', content_rowid='id');
+            INSERT INTO {index} ({index}) VALUES ('rebuild');
+            ", index=index, columns=column_list, table=table))?;
+    }
+
+    connection.execute_batch(&format!("
+        CREATE TRIGGER IF NOT EXISTS {index}_insert AFTER INSERT ON {table} BEGIN
+            INSERT INTO {index} (rowid, {columns}) VALUES (new.id, {new_values});
+        END;
+        CREATE TRIGGER IF NOT EXISTS {index}_delete AFTER DELETE ON {table} BEGIN
+            INSERT INTO {index} ({index}, rowid, {columns}) VALUES ('delete', old.id, {old_values});
+        END;
+        CREATE TRIGGER IF NOT EXISTS {index}_update AFTER UPDATE OF {columns} ON {table} BEGIN
+            INSERT INTO {index} ({index}, rowid, {columns}) VALUES ('delete', old.id, {old_values});
+            INSERT INTO {index} (rowid, {columns}) VALUES (new.id, {new_values});
+        END;
+        ", index=index, table=table, columns=column_list, new_values=new_values, old_values=old_values))?;
+    Ok(())
+}
+
+/// Check if the table has the specified column.
+fn has_column(connection: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut statement = connection.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = statement.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<result::Result<Vec<_>, _>>()?;
+    Ok(columns.iter().any(|name| name == column))
+}
+
+/// Check if the database has the specified table.
+fn has_table(connection: &Connection, table: &str) -> Result<bool> {
+    let count: i32 = connection.query_row("
+        SELECT COUNT(*)
+        FROM sqlite_master
+        WHERE type = 'table' AND name = $1
+        ", &[&table], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rusqlite::Connection;
+
+    use super::{has_column, latest_version, migrate, schema_version};
+
+    #[test]
+    fn migrate_new_database() {
+        let connection = Connection::open_in_memory().unwrap();
+        migrate(&connection, Path::new(":memory:")).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+        // Migrating again does nothing.
+        migrate(&connection, Path::new(":memory:")).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn migrate_unversioned_database() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("
+            CREATE TABLE bookmarks
+            ( id INTEGER PRIMARY KEY
+            , title TEXT NOT NULL
+            , url TEXT NOT NULL UNIQUE
+            , visit_count INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT INTO bookmarks (title, url) VALUES ('Rust', 'https://www.rust-lang.org/');
+        ").unwrap();
+        migrate(&connection, Path::new(":memory:")).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+        assert!(has_column(&connection, "bookmarks", "last_visit").unwrap());
+        let count: i32 = connection.query_row("
+            SELECT COUNT(*)
+            FROM bookmarks_fts
+            WHERE bookmarks_fts MATCH 'rust'
+            ", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn migrate_mark_tables_without_anchor() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("
+            CREATE TABLE marks
+            ( url TEXT NOT NULL
+            , mark INTEGER NOT NULL
+            , percentage INTEGER NOT NULL
+            , PRIMARY KEY (url, mark)
+            );
+            CREATE TABLE global_marks
+            ( mark INTEGER PRIMARY KEY
+            , url TEXT NOT NULL
+            , percentage INTEGER NOT NULL
+            );
+            INSERT INTO marks (url, mark, percentage) VALUES ('https://www.rust-lang.org/', 97, 50);
+        ").unwrap();
+        migrate(&connection, Path::new(":memory:")).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+        for table in &["marks", "global_marks"] {
+            assert!(has_column(&connection, table, "anchor").unwrap());
+            assert!(has_column(&connection, table, "anchor_offset").unwrap());
+        }
+        let (anchor, anchor_offset, percentage): (Option<String>, i32, i32) = connection.query_row("
+            SELECT anchor, anchor_offset, percentage
+            FROM marks
+            WHERE mark = 97
+            ", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        assert_eq!((anchor, anchor_offset, percentage), (None, 0, 50));
+        connection.execute("
+            INSERT INTO global_marks (mark, url, anchor, anchor_offset, percentage)
+            VALUES (65, 'https://www.rust-lang.org/', 'learn', 10, 20)
+            ", []).unwrap();
+    }
+
+    #[test]
+    fn newer_database() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.pragma_update(None, "user_version", &(latest_version() + 1)).unwrap();
+        assert!(migrate(&connection, Path::new(":memory:")).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Named sessions, which are sets of windows that can be saved and opened again.
+
+use std::fs::{File, create_dir_all, read_dir, remove_file};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use config_dir::ConfigDir;
+use errors::{Error, Result};
+use message_server::Privacy;
+use windows::OpenedWindow;
+
+/// The file, in the data directory, where the opened windows are saved to recover from a crash.
+const RECOVERY_FILE: &str = "session";
+/// The file, in the data directory, where the previous versions saved the URLs of the opened windows.
+const LEGACY_RECOVERY_FILE: &str = "urls";
+/// The directory, in the data directory, where the sessions are saved.
+const SESSION_DIR: &str = "sessions";
+
+/// A tab saved in a session.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SessionTab {
+    /// The serialized back/forward history of the tab, if available.
+    pub state: Option<String>,
+    pub url: String,
+}
+
+/// A window saved in a session.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(from = "SavedWindow")]
+pub struct SessionWindow {
+    /// The index of the tab shown in the window.
+    pub current_tab: usize,
+    pub privacy: Privacy,
+    pub tabs: Vec<SessionTab>,
+}
+
+impl SessionWindow {
+    /// Create a saved window from its tabs, without the tabs that have no URL.
+    /// Return None if no tab has a URL.
+    pub fn new(privacy: Privacy, tabs: Vec<SessionTab>, current_tab: usize) -> Option<Self> {
+        let current_tab = tabs.iter()
+            .take(current_tab)
+            .filter(|tab| !tab.url.is_empty())
+            .count();
+        let tabs: Vec<_> = tabs.into_iter()
+            .filter(|tab| !tab.url.is_empty())
+            .collect();
+        if tabs.is_empty() {
+            return None;
+        }
+        Some(SessionWindow {
+            current_tab: current_tab.min(tabs.len() - 1),
+            privacy,
+            tabs,
+        })
+    }
+
+    /// Get the URL of the tab shown in the window.
+    pub fn url(&self) -> &str {
+        self.tabs.get(self.current_tab)
+            .map(|tab| tab.url.as_str())
+            .unwrap_or_default()
+    }
+}
+
+/// A window as written in a session file.
+/// The previous versions saved a single tab per window, in the `state` and `url` fields.
+#[derive(Deserialize)]
+struct SavedWindow {
+    #[serde(default)]
+    current_tab: usize,
+    privacy: Privacy,
+    state: Option<String>,
+    #[serde(default)]
+    tabs: Vec<SessionTab>,
+    url: Option<String>,
+}
+
+impl From<SavedWindow> for SessionWindow {
+    fn from(window: SavedWindow) -> Self {
+        let mut tabs = window.tabs;
+        if let Some(url) = window.url {
+            tabs.push(SessionTab {
+                state: window.state,
+                url,
+            });
+        }
+        SessionWindow {
+            current_tab: window.current_tab,
+            privacy: window.privacy,
+            tabs,
+        }
+    }
+}
+
+/// A set of windows saved under a name.
+#[derive(Deserialize, Serialize)]
+pub struct Session {
+    pub windows: Vec<SessionWindow>,
+}
+
+impl Session {
+    /// Create a session from the opened windows.
+    /// The tabs without a URL are not saved.
+    pub fn new(windows: Vec<OpenedWindow>) -> Self {
+        let windows = windows.into_iter()
+            .filter_map(|window| SessionWindow::new(window.privacy, window.tabs, window.current_tab))
+            .collect();
+        Session {
+            windows,
+        }
+    }
+}
+
+/// Delete the session with the specified name.
+pub fn delete(config_dir: &ConfigDir, name: &str) -> Result<()> {
+    let path = session_path(config_dir, name)?;
+    if !path.exists() {
+        return Err(Error::from_string(format!("No session named {}", name)));
+    }
+    remove_file(path)?;
+    Ok(())
+}
+
+/// Get the names of the saved sessions, sorted alphabetically.
+pub fn list(config_dir: &ConfigDir) -> Vec<String> {
+    let mut names: Vec<_> = read_dir(config_dir.data_home().join(SESSION_DIR)).into_iter()
+        .flat_map(|entries| entries)
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Load the session with the specified name.
+pub fn load(config_dir: &ConfigDir, name: &str) -> Result<Session> {
+    let path = session_path(config_dir, name)?;
+    if !path.exists() {
+        return Err(Error::from_string(format!("No session named {}", name)));
+    }
+    read(&path)
+}
+
+/// Load the windows that were opened when the application last exited.
+/// It is empty if the application exited normally.
+/// The URL file of the previous versions is converted to the new format.
+pub fn load_recovery(config_dir: &ConfigDir) -> Result<Session> {
+    let path = config_dir.data_file(RECOVERY_FILE)?;
+    if path.exists() {
+        return read(&path);
+    }
+    let legacy_path = config_dir.data_file(LEGACY_RECOVERY_FILE)?;
+    if !legacy_path.exists() {
+        return Ok(Session::new(vec![]));
+    }
+    let session = read_legacy(&legacy_path)?;
+    write(&path, &session)?;
+    remove_file(legacy_path)?;
+    Ok(session)
+}
+
+/// Save the session under the specified name, replacing the existing one.
+pub fn save(config_dir: &ConfigDir, name: &str, session: &Session) -> Result<()> {
+    let path = session_path(config_dir, name)?;
+    write(&path, session)
+}
+
+/// Save the opened windows to be able to restore them after a crash.
+pub fn save_recovery(config_dir: &ConfigDir, session: &Session) -> Result<()> {
+    let path = config_dir.data_file(RECOVERY_FILE)?;
+    write(&path, session)
+}
+
+fn read(path: &Path) -> Result<Session> {
+    let mut file = File::open(path)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Read the URL file of the previous versions, containing one URL per line.
+fn read_legacy(path: &Path) -> Result<Session> {
+    let file = BufReader::new(File::open(path)?);
+    let mut windows = vec![];
+    for line in file.lines() {
+        let tab = SessionTab {
+            state: None,
+            url: line?,
+        };
+        windows.extend(SessionWindow::new(Privacy::Normal, vec![tab], 0));
+    }
+    Ok(Session {
+        windows,
+    })
+}
+
+/// Get the path of the file of a session, creating the session directory if needed.
+fn session_path(config_dir: &ConfigDir, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(Error::from_string(format!("Invalid session name: {}", name)));
+    }
+    let directory = config_dir.data_home().join(SESSION_DIR);
+    create_dir_all(&directory)?;
+    Ok(directory.join(name))
+}
+
+fn write(path: &Path, session: &Session) -> Result<()> {
+    let content = serde_json::to_string(session)?;
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{File, create_dir};
+    use std::io::Write;
+
+    use tempfile::Builder as TempFileBuilder;
+
+    use config_dir::ConfigDir;
+    use serde_json;
+
+    use message_server::Privacy;
+    use super::{Session, SessionTab, SessionWindow, delete, list, load, load_recovery, save, save_recovery};
+
+    fn tab(url: &str, state: Option<&str>) -> SessionTab {
+        SessionTab {
+            state: state.map(str::to_string),
+            url: url.to_string(),
+        }
+    }
+
+    fn urls(session: &Session) -> Vec<(usize, Vec<(&str, Option<&str>)>)> {
+        session.windows.iter()
+            .map(|window| {
+                let tabs = window.tabs.iter()
+                    .map(|tab| (tab.url.as_str(), tab.state.as_ref().map(String::as_str)))
+                    .collect();
+                (window.current_tab, tabs)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn session_windows() {
+        let tabs = vec![
+            tab("", None),
+            tab("https://example.com/", None),
+            tab("", None),
+            tab("https://example.org/", None),
+        ];
+        let window = SessionWindow::new(Privacy::Normal, tabs.clone(), 3).unwrap();
+        assert_eq!(window.tabs.len(), 2);
+        assert_eq!(window.current_tab, 1);
+        assert_eq!(window.url(), "https://example.org/");
+
+        let window = SessionWindow::new(Privacy::Normal, tabs, 2).unwrap();
+        assert_eq!(window.current_tab, 1);
+        assert!(SessionWindow::new(Privacy::Normal, vec![tab("", None)], 0).is_none());
+    }
+
+    #[test]
+    fn single_tab_windows() {
+        let session: Session = serde_json::from_str(r#"{"windows": [
+            {"privacy": "Normal", "state": "c3RhdGU=", "url": "https://example.com/"},
+            {"privacy": "Private", "state": null, "url": "https://example.org/"}
+        ]}"#).unwrap();
+        assert_eq!(urls(&session), vec![
+            (0, vec![("https://example.com/", Some("c3RhdGU="))]),
+            (0, vec![("https://example.org/", None)]),
+        ]);
+    }
+
+    #[test]
+    fn named_sessions() {
+        let temp_dir = TempFileBuilder::new().tempdir().unwrap();
+        let config_dir = ConfigDir::new(&temp_dir.path().to_str().map(str::to_string), &None).unwrap();
+        create_dir(config_dir.data_home()).unwrap();
+        let session = Session {
+            windows: vec![
+                SessionWindow::new(Privacy::Normal,
+                    vec![tab("https://example.com/", Some("c3RhdGU=")), tab("https://example.net/", None)], 1).unwrap(),
+                SessionWindow::new(Privacy::Container("work".to_string()), vec![tab("https://example.org/", None)], 0)
+                    .unwrap(),
+            ],
+        };
+        save(&config_dir, "work", &session).unwrap();
+        save(&config_dir, "empty", &Session { windows: vec![] }).unwrap();
+        assert_eq!(list(&config_dir), vec!["empty", "work"]);
+
+        let loaded = load(&config_dir, "work").unwrap();
+        assert_eq!(urls(&loaded), urls(&session));
+        assert!(loaded.windows[0].privacy == Privacy::Normal);
+        assert!(loaded.windows[1].privacy == Privacy::Container("work".to_string()));
+
+        delete(&config_dir, "work").unwrap();
+        assert_eq!(list(&config_dir), vec!["empty"]);
+        assert!(load(&config_dir, "work").is_err());
+        assert!(delete(&config_dir, "work").is_err());
+        assert!(save(&config_dir, "../work", &session).is_err());
+    }
+
+    #[test]
+    fn recovery() {
+        let temp_dir = TempFileBuilder::new().tempdir().unwrap();
+        let config_dir = ConfigDir::new(&temp_dir.path().to_str().map(str::to_string), &None).unwrap();
+        create_dir(config_dir.data_home()).unwrap();
+        assert!(load_recovery(&config_dir).unwrap().windows.is_empty());
+
+        let session = Session {
+            windows: vec![SessionWindow::new(Privacy::Private, vec![tab("https://example.com/", None)], 0).unwrap()],
+        };
+        save_recovery(&config_dir, &session).unwrap();
+        let loaded = load_recovery(&config_dir).unwrap();
+        assert_eq!(urls(&loaded), urls(&session));
+        assert!(loaded.windows[0].privacy == Privacy::Private);
+    }
+
+    #[test]
+    fn legacy_recovery() {
+        let temp_dir = TempFileBuilder::new().tempdir().unwrap();
+        let config_dir = ConfigDir::new(&temp_dir.path().to_str().map(str::to_string), &None).unwrap();
+        create_dir(config_dir.data_home()).unwrap();
+        let legacy_path = config_dir.data_file("urls").unwrap();
+        let mut file = File::create(&legacy_path).unwrap();
+        writeln!(file, "https://example.com/").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "https://example.org/").unwrap();
+
+        let session = load_recovery(&config_dir).unwrap();
+        assert_eq!(urls(&session),
+            vec![(0, vec![("https://example.com/", None)]), (0, vec![("https://example.org/", None)])]);
+        assert!(session.windows.iter().all(|window| window.privacy == Privacy::Normal));
+        assert!(!legacy_path.exists());
+        assert_eq!(urls(&load_recovery(&config_dir).unwrap()), urls(&session));
+    }
+}
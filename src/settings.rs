@@ -40,11 +40,35 @@ impl CookieAcceptPolicy {
     }
 }
 
+/// When to show the tab bar.
+#[derive(Clone, Setting)]
+pub enum TabBar {
+    Always,
+    #[default]
+    Multiple,
+    Never,
+}
+
+/// Whether the private windows share a web context.
+#[derive(Clone, PartialEq, Setting)]
+pub enum PrivateContext {
+    /// All the private windows share their cookies and website data.
+    #[default]
+    Shared,
+    /// Every private window opened with private-win-open has its own web context, shared with the
+    /// windows it opens.
+    PerWindow,
+}
+
 #[derive(Default, Settings)]
 pub struct AppSettings {
+    pub config_watch: bool,
     pub cookie_accept: CookieAcceptPolicy,
     pub hint_chars: String,
     pub home_page: String,
+    pub open_in_tab: bool,
+    pub private_context: PrivateContext,
+    pub tab_bar: TabBar,
     pub webkit_allow_file_access_from_file_urls: bool,
     pub webkit_allow_modal_dialogs: bool,
     pub webkit_auto_load_images: bool,
@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Websites always opened in a private window or in a container, like `private-site *.bank.com`
+//! and `container-domain work example.com`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use url::Url;
+
+use message_server::Privacy;
+use urls::matches_pattern;
+
+struct Rules {
+    /// The containers in which the domains are always opened.
+    container_domains: HashMap<String, String>,
+    /// The privacy of the window.
+    privacy: Privacy,
+    /// The URL patterns of the websites always opened in a private window.
+    private_sites: Vec<String>,
+}
+
+/// The private sites and the container domains of a window.
+/// It is shared with the web views of the window, which move the navigations to another window
+/// before sending their request.
+#[derive(Clone)]
+pub struct SitePrivacy {
+    rules: Rc<RefCell<Rules>>,
+}
+
+impl SitePrivacy {
+    /// Create the rules of a window having the specified privacy.
+    pub fn new(privacy: Privacy) -> Self {
+        SitePrivacy {
+            rules: Rc::new(RefCell::new(Rules {
+                container_domains: HashMap::new(),
+                privacy,
+                private_sites: vec![],
+            })),
+        }
+    }
+
+    /// Pin a domain and its subdomains to a container.
+    pub fn add_container_domain(&self, domain: &str, container: &str) {
+        self.rules.borrow_mut().container_domains.insert(domain.to_lowercase(), container.to_string());
+    }
+
+    /// Add an URL pattern to the list of websites always opened in a private window.
+    pub fn add_private_site(&self, pattern: &str) {
+        self.rules.borrow_mut().private_sites.push(pattern.to_string());
+    }
+
+    /// Remove every rule.
+    pub fn clear(&self) {
+        let mut rules = self.rules.borrow_mut();
+        rules.container_domains.clear();
+        rules.private_sites.clear();
+    }
+
+    /// Check if the URL must always be opened in a private window.
+    pub fn is_private_site(&self, url: &str) -> bool {
+        self.rules.borrow().private_sites.iter()
+            .any(|pattern| matches_pattern(url, pattern))
+    }
+
+    /// Get the container to which the domain of the URL is pinned, if any.
+    pub fn pinned_container(&self, url: &str) -> Option<Privacy> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        self.rules.borrow().container_domains.iter()
+            .filter(|&(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))
+            // Use the most specific domain.
+            .max_by_key(|&(domain, _)| domain.len())
+            .map(|(_, container)| Privacy::Container(container.clone()))
+    }
+
+    /// Get the window in which the URL must be opened when it is a private site or is pinned to
+    /// another container.
+    /// The private windows are never left.
+    pub fn redirected_privacy(&self, url: &str) -> Option<Privacy> {
+        let privacy = self.rules.borrow().privacy.clone();
+        if privacy == Privacy::Private {
+            return None;
+        }
+        if self.is_private_site(url) {
+            return Some(Privacy::Private);
+        }
+        self.pinned_container(url)
+            .filter(|container| *container != privacy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use message_server::Privacy;
+    use super::SitePrivacy;
+
+    fn container(name: &str) -> Option<Privacy> {
+        Some(Privacy::Container(name.to_string()))
+    }
+
+    #[test]
+    fn pinned_containers() {
+        let site_privacy = SitePrivacy::new(Privacy::Normal);
+        site_privacy.add_container_domain("Example.com", "work");
+        site_privacy.add_container_domain("shop.example.com", "shopping");
+        assert!(site_privacy.pinned_container("https://example.com/") == container("work"));
+        assert!(site_privacy.pinned_container("https://www.EXAMPLE.com/page") == container("work"));
+        assert!(site_privacy.pinned_container("https://cart.shop.example.com/") == container("shopping"));
+        assert!(site_privacy.pinned_container("https://notexample.com/").is_none());
+        assert!(site_privacy.pinned_container("not an url").is_none());
+    }
+
+    #[test]
+    fn redirected_privacy() {
+        let site_privacy = SitePrivacy::new(Privacy::Container("work".to_string()));
+        site_privacy.add_container_domain("example.com", "work");
+        site_privacy.add_container_domain("example.org", "personal");
+        site_privacy.add_private_site("*.bank.com/*");
+        assert!(site_privacy.redirected_privacy("https://example.com/").is_none());
+        assert!(site_privacy.redirected_privacy("https://example.org/") == container("personal"));
+        assert!(site_privacy.redirected_privacy("https://www.bank.com/login") == Some(Privacy::Private));
+        assert!(site_privacy.redirected_privacy("https://example.net/").is_none());
+
+        site_privacy.clear();
+        assert!(site_privacy.redirected_privacy("https://www.bank.com/login").is_none());
+
+        let site_privacy = SitePrivacy::new(Privacy::Private);
+        site_privacy.add_container_domain("example.org", "personal");
+        site_privacy.add_private_site("*.bank.com/*");
+        assert!(site_privacy.redirected_privacy("https://example.org/").is_none());
+        assert!(site_privacy.is_private_site("https://www.bank.com/login"));
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Settings overridden for some websites, like `set-for example.com webkit-enable-javascript = false`.
+
+use std::cell::RefCell;
+use std::mem::discriminant;
+use std::rc::Rc;
+
+use mg_settings::Value;
+use mg_settings::settings::Settings;
+
+use settings::{AppSettings, AppSettingsVariant};
+use urls::{host, matches_pattern};
+
+/// A setting overridden for the URLs matching a pattern.
+#[derive(Clone)]
+struct SiteSetting {
+    name: String,
+    pattern: String,
+    setting: AppSettingsVariant,
+}
+
+/// The settings overridden for some websites.
+/// It is shared by the windows and their web views, which apply the settings on navigation.
+#[derive(Clone)]
+pub struct SiteSettings {
+    rules: Rc<RefCell<Vec<SiteSetting>>>,
+}
+
+impl SiteSettings {
+    pub fn new() -> Self {
+        SiteSettings {
+            rules: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    /// Override a setting for the URLs matching the pattern.
+    /// The pattern is either a domain, which also matches its subdomains, or an URL pattern
+    /// containing `*` or `/`.
+    pub fn add(&self, pattern: &str, name: &str, value: &str) -> Result<(), String> {
+        check_site_setting(name)?;
+        let setting = AppSettings::to_variant(name, parse_value(value))
+            .map_err(|_| format!("invalid setting {} = {}", name, value))?;
+        self.rules.borrow_mut().push(SiteSetting {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            setting,
+        });
+        Ok(())
+    }
+
+    /// Remove all the rules.
+    pub fn clear(&self) {
+        self.rules.borrow_mut().clear();
+    }
+
+    /// Get the settings, with their name, to apply to the URL.
+    /// When a setting is overridden by many rules, the last one wins.
+    pub fn matching(&self, url: &str) -> Vec<(String, AppSettingsVariant)> {
+        let mut settings: Vec<(String, AppSettingsVariant)> = vec![];
+        for rule in self.rules.borrow().iter().filter(|rule| matches_site(url, &rule.pattern)) {
+            settings.retain(|&(_, ref setting)| discriminant(setting) != discriminant(&rule.setting));
+            settings.push((rule.name.clone(), rule.setting.clone()));
+        }
+        settings
+    }
+}
+
+/// Check if the setting can be overridden for a website.
+/// Only the webkit settings can, since they are applied to a single web view. The cookie policy,
+/// for instance, is shared by every web view using the same cookies.
+fn check_site_setting(name: &str) -> Result<(), String> {
+    if name == "cookie-accept" {
+        Err("cookie-accept cannot be set for a website since it applies to every window sharing the same cookies"
+            .to_string())
+    }
+    else if !name.starts_with("webkit-") {
+        Err(format!("{} cannot be set for a website: only the webkit-* settings can", name))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Check if the URL is on the website specified by the pattern.
+fn matches_site(url: &str, pattern: &str) -> bool {
+    if pattern.contains('*') || pattern.contains('/') {
+        matches_pattern(url, pattern)
+    }
+    else {
+        host(url)
+            .map(|host| host == pattern || host.ends_with(&format!(".{}", pattern)))
+            .unwrap_or(false)
+    }
+}
+
+/// Parse the value of a setting like in the config files.
+pub fn parse_value(value: &str) -> Value {
+    let value = value.trim();
+    if let Ok(boolean) = value.parse() {
+        Value::Bool(boolean)
+    }
+    else if let Ok(integer) = value.parse() {
+        Value::Int(integer)
+    }
+    else if let Ok(float) = value.parse() {
+        Value::Float(float)
+    }
+    else {
+        Value::Str(value.trim_matches('"').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mg_settings::Value;
+
+    use super::{check_site_setting, matches_site, parse_value};
+
+    #[test]
+    fn match_sites() {
+        assert!(matches_site("https://example.com/page", "example.com"));
+        assert!(matches_site("https://www.example.com/", "example.com"));
+        assert!(!matches_site("https://notexample.com/", "example.com"));
+        assert!(matches_site("https://example.com/videos/1", "example.com/videos/*"));
+        assert!(!matches_site("https://example.com/", "example.com/videos/*"));
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse_value("false"), Value::Bool(false));
+        assert_eq!(parse_value(" 16"), Value::Int(16));
+        assert_eq!(parse_value("1.5"), Value::Float(1.5));
+        assert_eq!(parse_value("Mozilla/5.0 (X11)"), Value::Str("Mozilla/5.0 (X11)".to_string()));
+        assert_eq!(parse_value("\"no-third-party\""), Value::Str("no-third-party".to_string()));
+    }
+
+    #[test]
+    fn site_settings() {
+        assert!(check_site_setting("webkit-enable-javascript").is_ok());
+        assert!(check_site_setting("cookie-accept").is_err());
+        assert!(check_site_setting("home-page").is_err());
+    }
+}
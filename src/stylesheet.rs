@@ -38,10 +38,11 @@ pub fn get_stylesheet_and_whitelist(content: &str) -> (Cow<str>, Vec<String>) {
                     let parameter = parameter.trim_matches(',');
                     whitelist.append(&mut get_urls_from_parameter(parameter));
                 }
-                let stylesheet: String = content.chars().skip_while(|&c| c != '{').skip(1).collect();
-                let mut lines: Vec<_> = stylesheet.lines().collect();
-                lines.pop(); // Remove the last line which contains }.
-                lines.join("\n").into()
+                match document_block(content) {
+                    Some((start, Some(end))) => content[start + 1..end].into(),
+                    Some((start, None)) => content[start + 1..].into(),
+                    None => "".into(),
+                }
             }
             else {
                 content.into()
@@ -53,6 +54,120 @@ pub fn get_stylesheet_and_whitelist(content: &str) -> (Cow<str>, Vec<String>) {
     (stylesheet, whitelist)
 }
 
+/// Check the syntax of the @document header of a stylesheet, if any.
+/// The error is returned with its line number.
+pub fn check_document_header(content: &str) -> Result<(), (usize, String)> {
+    let first_line = content.lines().position(|line| !line.trim().is_empty()).unwrap_or(0) + 1;
+    let mut words = content.split_whitespace();
+    if words.next() != Some("@document") {
+        return Ok(());
+    }
+    let mut has_block = false;
+    for word in words {
+        if word == "{" {
+            has_block = true;
+            break;
+        }
+        let parameter = word.trim_matches(',');
+        if !is_document_parameter(parameter) {
+            return Err((first_line, format!("invalid @document parameter {}", parameter)));
+        }
+    }
+    if !has_block {
+        return Err((first_line, "expecting { after the @document parameters".to_string()));
+    }
+    // The block of the @document must be closed on the last line.
+    let last_line = content.trim_end().lines().count();
+    match document_block(content) {
+        Some((_, Some(end))) => {
+            let end_line = line_number(content, end);
+            if end_line != last_line {
+                Err((end_line, "the @document block must be closed on the last line".to_string()))
+            }
+            else if !content[end + 1..].trim().is_empty() {
+                Err((end_line, "unexpected content after the @document block".to_string()))
+            }
+            else {
+                Ok(())
+            }
+        },
+        _ => Err((last_line, "expecting } on the last line to close the @document".to_string())),
+    }
+}
+
+/// Find the braces opening and closing the @document block of a stylesheet.
+/// Returns their byte positions, without the closing one when the block is not closed.
+fn document_block(content: &str) -> Option<(usize, Option<usize>)> {
+    let braces = braces(content);
+    let start =
+        match braces.first() {
+            Some(&(start, '{')) => start,
+            _ => return None,
+        };
+    let mut depth = 0;
+    for (index, brace) in braces {
+        if brace == '{' {
+            depth += 1;
+        }
+        else {
+            depth -= 1;
+            if depth == 0 {
+                return Some((start, Some(index)));
+            }
+        }
+    }
+    Some((start, None))
+}
+
+/// Get the braces of a stylesheet with their byte position, ignoring the ones in the strings and
+/// the comments.
+fn braces(content: &str) -> Vec<(usize, char)> {
+    let mut braces = vec![];
+    let mut quote = None;
+    let mut characters = content.char_indices().peekable();
+    while let Some((index, character)) = characters.next() {
+        if let Some(quote_character) = quote {
+            if character == '\\' {
+                // Skip the escaped character.
+                characters.next();
+            }
+            else if character == quote_character {
+                quote = None;
+            }
+            continue;
+        }
+        match character {
+            '"' | '\'' => quote = Some(character),
+            '/' if characters.peek().map(|&(_, next)| next) == Some('*') => {
+                characters.next();
+                let mut previous = None;
+                for (_, character) in characters.by_ref() {
+                    if previous == Some('*') && character == '/' {
+                        break;
+                    }
+                    previous = Some(character);
+                }
+            },
+            '{' | '}' => braces.push((index, character)),
+            _ => (),
+        }
+    }
+    braces
+}
+
+/// Get the line number of a byte position.
+fn line_number(content: &str, position: usize) -> usize {
+    content[..position].matches('\n').count() + 1
+}
+
+/// Check if the parameter is a quoted URL or a domain() function.
+fn is_document_parameter(parameter: &str) -> bool {
+    let is_quoted = |string: &str| string.len() >= 2 && string.starts_with('"') && string.ends_with('"');
+    is_quoted(parameter) ||
+        (parameter.starts_with("domain(") && parameter.ends_with(')') &&
+            is_quoted(&parameter["domain(".len()..parameter.len() - 1]))
+}
+
 /// Get the urls from a paramater.
 fn get_urls_from_parameter(parameter: &str) -> Vec<String> {
     let mut whitelist = vec![];
@@ -74,3 +189,36 @@ fn get_urls_from_parameter(parameter: &str) -> Vec<String> {
     }
     whitelist
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{check_document_header, get_stylesheet_and_whitelist};
+
+    #[test]
+    fn document_header() {
+        assert!(check_document_header("body { color: red; }").is_ok());
+        assert!(check_document_header("@document domain(\"example.com\"), \"https://example.org/\" {\nbody {}\n}").is_ok());
+        assert_eq!(check_document_header("\n@document example.com {\nbody {}\n}"),
+            Err((2, "invalid @document parameter example.com".to_string())));
+        assert_eq!(check_document_header("@document domain(\"example.com\"){\nbody {}\n}"),
+            Err((1, "invalid @document parameter domain(\"example.com\"){".to_string())));
+        assert_eq!(check_document_header("@document domain(\"example.com\") {\nbody { color: red; }"),
+            Err((2, "expecting } on the last line to close the @document".to_string())));
+        assert_eq!(check_document_header("@document domain(\"example.com\") {\n}\nbody { color: red; }\n"),
+            Err((2, "the @document block must be closed on the last line".to_string())));
+        assert!(check_document_header("@document \"https://example.org/\" {\na::after { content: \"}\"; }\n/* } */\n}")
+            .is_ok());
+    }
+
+    #[test]
+    fn stylesheet_and_whitelist() {
+        let (stylesheet, whitelist) =
+            get_stylesheet_and_whitelist("@document \"https://example.org/\" {\na::after { content: \"}\"; }\n}\n");
+        assert_eq!(stylesheet, "\na::after { content: \"}\"; }\n");
+        assert_eq!(whitelist, vec!["https://example.org/".to_string()]);
+
+        let (stylesheet, whitelist) = get_stylesheet_and_whitelist("body { color: red; }");
+        assert_eq!(stylesheet, "body { color: red; }");
+        assert!(whitelist.is_empty());
+    }
+}
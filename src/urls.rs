@@ -27,12 +27,19 @@ use url::percent_encoding::percent_decode;
 
 /// Get a file URL from the input if the file exists, otherwise return the input as is.
 pub fn canonicalize_url(url: &str) -> String {
-    if Path::new(url).exists() {
-        if let Ok(path) = env::current_dir() {
-            let url = path.join(url);
-            if let Some(url) = url.to_str() {
-                return format!("file://{}", url);
-            }
+    match env::current_dir() {
+        Ok(directory) => canonicalize_url_in(url, &directory),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Get a file URL from the input if the file exists, relative to the specified directory,
+/// otherwise return the input as is.
+pub fn canonicalize_url_in(url: &str, directory: &Path) -> String {
+    let path = directory.join(url);
+    if path.exists() {
+        if let Some(path) = path.to_str() {
+            return format!("file://{}", path);
         }
     }
     url.to_string()
@@ -69,6 +76,37 @@ pub fn host(url: &str) -> Option<String> {
     })
 }
 
+/// Check if the URL matches the pattern, where `*` matches any sequence of characters.
+/// The scheme of the URL is ignored when the pattern does not contain one.
+pub fn matches_pattern(url: &str, pattern: &str) -> bool {
+    let url =
+        if pattern.contains("://") {
+            url
+        }
+        else {
+            url.splitn(2, "://").last().unwrap_or(url)
+        };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !url.starts_with(first) {
+        return false;
+    }
+    let mut rest = &url[first.len()..];
+    let parts: Vec<_> = parts.collect();
+    match parts.split_last() {
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(last)
+        },
+    }
+}
+
 /// Check if the input string looks like a URL.
 pub fn is_url(input: &str) -> bool {
     Url::parse(input).is_ok() || (Url::parse(&format!("http://{}", input)).is_ok() &&
@@ -151,7 +189,18 @@ pub fn offset(url: &str, inc_offset: i32) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::offset;
+    use super::{matches_pattern, offset};
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("https://bank.example.com/login", "bank.example.com/*"));
+        assert!(matches_pattern("https://www.bank.example.com/", "*.example.com/*"));
+        assert!(matches_pattern("https://bank.example.com/login", "https://*/login"));
+        assert!(!matches_pattern("http://bank.example.com/login", "https://*/login"));
+        assert!(!matches_pattern("https://example.com.evil.org/", "*.example.com/*"));
+        assert!(!matches_pattern("https://example.com/", "example.com"));
+        assert!(matches_pattern("https://example.com/", "example.com/"));
+    }
 
     #[test]
     fn test_offset() {
@@ -31,12 +31,14 @@ macro_rules! handle_app_error {
 mod settings;
 
 use std::cell::Cell;
-use std::fs::{File, read_dir};
+use std::fs::{File, create_dir_all, read_dir};
 use std::io::Read;
+use std::path::Path;
 use std::rc::Rc;
 
 use cairo::{Context, Format, ImageSurface};
-use glib::Cast;
+use gio::Cancellable;
+use glib::{Cast, TimeSpan};
 use gtk::{traits::WidgetExt, Window};
 use relm::{Relm, Widget};
 use relm_derive::widget;
@@ -67,6 +69,9 @@ use webkit2gtk::{
     WebInspector,
     WebInspectorExt,
     WebViewExt,
+    WebsiteDataManager,
+    WebsiteDataManagerExtManual,
+    WebsiteDataTypes,
 };
 use webkit2gtk::NavigationType::{LinkClicked, Other};
 use webkit2gtk::PolicyDecisionType::{self, NavigationAction, Response};
@@ -78,17 +83,29 @@ use webkit2gtk::UserStyleLevel::User;
 use config_dir::ConfigDir;
 use errors::Result;
 use file;
+use message_server::Privacy;
 use self::Msg::*;
 use settings::AppSettingsVariant;
+use site_privacy::SitePrivacy;
+use site_settings::SiteSettings;
 use stylesheet::get_stylesheet_and_whitelist;
 
+/// The directory, in the data directory, containing the cookies and website data of the containers.
+const CONTAINER_DIR: &str = "containers";
+
 pub struct Model {
     config_dir: ConfigDir,
     context: WebContext,
+    /// The values of the settings to restore when leaving a website overriding them.
+    default_settings: Vec<AppSettingsVariant>,
     inspector_shown: Rc<Cell<bool>>,
     open_in_new_window: Rc<Cell<bool>>,
+    /// The settings overridden for the current website.
+    overridden_settings: Vec<AppSettingsVariant>,
     relm: Relm<WebView>,
     search_backwards: bool,
+    site_privacy: SitePrivacy,
+    site_settings: SiteSettings,
 }
 
 #[derive(Msg)]
@@ -96,6 +113,7 @@ pub enum Msg {
     AddScripts,
     AddStylesheets,
     AppError(String),
+    ApplySiteSettings,
     Close,
     EndSearch,
     EnterFullScreen,
@@ -113,6 +131,12 @@ pub enum Msg {
     PageZoomNormal,
     PageZoomOut,
     PermissionRequest(PermissionRequest),
+    /// A navigation was stopped because the URL must be opened in a window with this privacy.
+    Redirect(String, Privacy),
+    /// Add the user scripts again and run them in the current page.
+    ReloadScripts,
+    /// Go back to the default settings, before the configuration is applied again.
+    ResetSettings,
     SearchBackward(bool),
     SetOpenInNewWindow(bool),
     ShowInspector,
@@ -131,14 +155,20 @@ impl Widget for WebView {
         }
     }
 
-    fn model(relm: &Relm<Self>, (config_dir, context): (ConfigDir, WebContext)) -> Model {
+    fn model(relm: &Relm<Self>, (config_dir, context, site_privacy, site_settings): (ConfigDir, WebContext,
+        SitePrivacy, SiteSettings)) -> Model
+    {
         Model {
             config_dir,
             context,
+            default_settings: vec![],
             inspector_shown: Rc::new(Cell::new(false)),
             open_in_new_window: Rc::new(Cell::new(false)),
+            overridden_settings: vec![],
             relm: relm.clone(),
             search_backwards: false,
+            site_privacy,
+            site_settings,
         }
     }
 
@@ -147,6 +177,7 @@ impl Widget for WebView {
             AddScripts => handle_app_error!(self.add_scripts()),
             AddStylesheets => handle_app_error!(self.add_stylesheets()),
             AppError(_) => (), // To be listened by the user.
+            ApplySiteSettings => self.apply_site_settings(),
             // To be listened by the user.
             Close => (),
             EndSearch => handle_app_error!(self.finish_search()),
@@ -169,10 +200,14 @@ impl Widget for WebView {
             PageZoomOut => self.show_zoom(self.zoom_out()),
             // To be listened by the user.
             PermissionRequest(_) => (),
+            // To be listened by the user.
+            Redirect(_, _) => (),
+            ReloadScripts => handle_app_error!(self.reload_scripts()),
+            ResetSettings => self.reset_settings(),
             SearchBackward(search_backwards) => self.model.search_backwards = search_backwards,
             SetOpenInNewWindow(open_in_new_window) => self.set_open_in_new_window(open_in_new_window),
             ShowInspector => self.show_inspector(),
-            WebViewSettingChanged(setting) => self.setting_changed(setting),
+            WebViewSettingChanged(setting) => self.global_setting_changed(setting),
             // To be listened by the user.
             ZoomChange(_) => (),
         }
@@ -186,8 +221,9 @@ impl Widget for WebView {
         }) {
             close => Close,
             vexpand: true,
-            decide_policy(_, policy_decision, policy_decision_type) with (open_in_new_window, relm) =>
-                return WebView::decide_policy(&policy_decision, &policy_decision_type, &open_in_new_window, &relm),
+            decide_policy(_, policy_decision, policy_decision_type) with (open_in_new_window, relm, site_privacy) =>
+                return WebView::decide_policy(&policy_decision, &policy_decision_type, &open_in_new_window,
+                    &site_privacy, &relm),
             enter_fullscreen => (EnterFullScreen, false),
             leave_fullscreen => (LeaveFullScreen, false),
             permission_request(_, request) => (PermissionRequest(request.clone()), true),
@@ -197,7 +233,9 @@ impl Widget for WebView {
 
 impl WebView {
     /// Add the user scripts.
-    fn add_scripts(&self) -> Result<()> {
+    /// Returns their content.
+    fn add_scripts(&self) -> Result<Vec<String>> {
+        let mut scripts = vec![];
         if let Some(content_manager) = self.widgets.view.user_content_manager() {
             content_manager.remove_all_scripts();
             let script_path = self.model.config_dir.config_file("scripts")?;
@@ -208,9 +246,10 @@ impl WebView {
                 // TODO: support whitelist as a comment in the script.
                 let script = UserScript::new(&content, AllFrames, End, &[], &[]);
                 content_manager.add_script(&script);
+                scripts.push(content);
             }
         }
-        Ok(())
+        Ok(scripts)
     }
 
     /// Add the user stylesheets.
@@ -231,11 +270,20 @@ impl WebView {
         Ok(())
     }
 
+    /// Add the user scripts again and run them in the main frame of the current page, which was
+    /// loaded with the previous scripts.
+    fn reload_scripts(&self) -> Result<()> {
+        for script in self.add_scripts()? {
+            self.widgets.view.run_javascript(&script, None::<&Cancellable>, |_| ());
+        }
+        Ok(())
+    }
+
     fn decide_policy(policy_decision: &PolicyDecision, policy_decision_type: &PolicyDecisionType,
-        open_in_new_window: &Rc<Cell<bool>>, relm: &Relm<WebView>) -> bool
+        open_in_new_window: &Rc<Cell<bool>>, site_privacy: &SitePrivacy, relm: &Relm<WebView>) -> bool
     {
         if *policy_decision_type == NavigationAction {
-            Self::handle_navigation_action(policy_decision, open_in_new_window, relm)
+            Self::handle_navigation_action(policy_decision, open_in_new_window, site_privacy, relm)
         }
         else if *policy_decision_type == Response {
             Self::handle_response(policy_decision)
@@ -271,8 +319,10 @@ impl WebView {
     }
 
     /// Handle follow link in new window.
+    /// Move the navigations to private sites or to domains pinned to another container to another
+    /// window, before their request is sent.
     fn handle_navigation_action(policy_decision: &PolicyDecision, open_in_new_window: &Rc<Cell<bool>>,
-        relm: &Relm<WebView>) -> bool
+        site_privacy: &SitePrivacy, relm: &Relm<WebView>) -> bool
     {
         let policy_decision = policy_decision.clone();
         if let Ok(policy_decision) = policy_decision.downcast::<NavigationPolicyDecision>() {
@@ -292,6 +342,21 @@ impl WebView {
                     return true;
                 }
             }
+
+            // The frames of a page are also loaded with the Other navigation type, so these
+            // navigations are only checked when they start loading in the main frame.
+            // The server redirections keep the navigation type of the original request.
+            if navigation_type != Other {
+                let url = policy_decision.request()
+                    .and_then(|request| request.uri());
+                if let Some(url) = url {
+                    if let Some(privacy) = site_privacy.redirected_privacy(&url) {
+                        policy_decision.ignore();
+                        relm.stream().emit(Redirect(url.to_string(), privacy));
+                        return true;
+                    }
+                }
+            }
         }
         false
     }
@@ -310,11 +375,16 @@ impl WebView {
     }
 
     /// Create the context and initialize the web extension.
-    pub fn initialize_web_extension(config_dir: &ConfigDir) -> (WebContext, WebContext) {
-        let private_context = WebContext::new_ephemeral();
-        setup_context(&private_context);
-
-        let context = WebContext::default().unwrap();
+    pub fn initialize_web_extension(config_dir: &ConfigDir) -> WebContext {
+        // A named profile also stores the local storage, the cache and the other website data in
+        // its own directory.
+        let context =
+            if config_dir.profile().is_some() {
+                isolated_context(&config_dir.data_home())
+            }
+            else {
+                WebContext::default().unwrap()
+            };
         setup_context(&context);
 
         if let Ok(cookie_path) = config_dir.data_file("cookies") {
@@ -326,7 +396,43 @@ impl WebView {
             // TODO: warn.
         }
 
-        (context, private_context)
+        context
+    }
+
+    /// Create an ephemeral web context for private windows.
+    pub fn private_context() -> WebContext {
+        let context = WebContext::new_ephemeral();
+        setup_context(&context);
+        context
+    }
+
+    /// Delete the cookies, the cache and the other website data of a web context.
+    pub fn clear_website_data(context: &WebContext) {
+        if let Some(data_manager) = context.website_data_manager() {
+            data_manager.clear(WebsiteDataTypes::ALL, TimeSpan(0), None::<&Cancellable>, |result| {
+                if let Err(error) = result {
+                    error!("Cannot clear the private website data: {}", error);
+                }
+            });
+        }
+    }
+
+    /// Create the web context of a container, which stores its cookies and website data in its own
+    /// directory.
+    pub fn container_context(config_dir: &ConfigDir, name: &str) -> WebContext {
+        let directory = config_dir.data_home().join(CONTAINER_DIR).join(name);
+        if let Err(error) = create_dir_all(&directory) {
+            error!("Cannot create the directory of container {}: {}", name, error);
+        }
+        let context = isolated_context(&directory);
+        setup_context(&context);
+
+        if let Some(cookie_manager) = context.cookie_manager() {
+            cookie_manager.set_persistent_storage(&directory.join("cookies").to_string_lossy(),
+                CookiePersistentStorage::Sqlite);
+        }
+
+        context
     }
 
     /// Open the specified URL.
@@ -448,6 +554,15 @@ fn set_context_ext_dir(context: &WebContext) {
     context.set_web_extensions_directory("nuon-web-extension/target/debug");
 }
 
+/// Create a web context storing its website data in the specified directory.
+fn isolated_context(directory: &Path) -> WebContext {
+    let data_manager = WebsiteDataManager::builder()
+        .base_data_directory(&directory.join("website-data").to_string_lossy())
+        .base_cache_directory(&directory.join("cache").to_string_lossy())
+        .build();
+    WebContext::with_website_data_manager(&data_manager)
+}
+
 fn setup_context(context: &WebContext) {
     set_context_ext_dir(&context);
 
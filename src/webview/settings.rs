@@ -19,11 +19,21 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use settings::{AppSettingsVariant, CookieAcceptPolicy};
+use std::mem::{discriminant, replace};
+
+use glib::ObjectExt;
+use mg_settings::Value;
+use mg_settings::settings::Settings;
+
+use settings::{AppSettings, AppSettingsVariant, CookieAcceptPolicy};
 use settings::AppSettingsVariant::{
+    ConfigWatch,
     CookieAccept,
     HintChars,
     HomePage,
+    OpenInTab,
+    PrivateContext,
+    TabBar,
     WebkitAllowFileAccessFromFileUrls,
     WebkitAllowModalDialogs,
     WebkitAutoLoadImages,
@@ -77,12 +87,87 @@ use settings::AppSettingsVariant::{
 use super::WebView;
 use webkit2gtk::{
     CookieManagerExt,
+    Settings,
     SettingsExt,
     WebContextExt,
     WebViewExt,
 };
 
 impl WebView {
+    /// Apply the settings overridden for the current URL and restore the ones overridden for the
+    /// previous website.
+    pub fn apply_site_settings(&mut self) {
+        let url = self.widgets.view.uri().map(|url| url.to_string()).unwrap_or_default();
+        let site_settings = self.model.site_settings.matching(&url);
+        let previous = replace(&mut self.model.overridden_settings, vec![]);
+        for setting in previous {
+            let still_overridden = site_settings.iter()
+                .any(|&(_, ref site_setting)| discriminant(site_setting) == discriminant(&setting));
+            if !still_overridden {
+                let default = self.model.default_settings.iter()
+                    .find(|default| discriminant(*default) == discriminant(&setting))
+                    .cloned();
+                if let Some(default) = default {
+                    self.setting_changed(default);
+                }
+            }
+        }
+        for (name, setting) in site_settings {
+            let has_default = self.model.default_settings.iter()
+                .any(|default| discriminant(default) == discriminant(&setting));
+            if !has_default {
+                if let Some(default) = self.current_setting(&name) {
+                    self.model.default_settings.push(default);
+                }
+            }
+            self.setting_changed(setting.clone());
+            self.model.overridden_settings.push(setting);
+        }
+    }
+
+    /// Save the new value of a setting, only applying it when the current website does not
+    /// override it.
+    pub fn global_setting_changed(&mut self, setting: AppSettingsVariant) {
+        self.model.default_settings.retain(|default| discriminant(default) != discriminant(&setting));
+        self.model.default_settings.push(setting.clone());
+        let overridden = self.model.overridden_settings.iter()
+            .any(|overridden| discriminant(overridden) == discriminant(&setting));
+        if !overridden {
+            self.setting_changed(setting);
+        }
+    }
+
+    /// Use the default webkit settings and cookie policy.
+    /// The settings overridden for the current website are applied again afterwards.
+    pub fn reset_settings(&mut self) {
+        self.widgets.view.set_settings(&Settings::new());
+        self.set_cookie_accept(&CookieAcceptPolicy::default());
+        self.model.default_settings.clear();
+        self.model.overridden_settings.clear();
+    }
+
+    /// Get the current value of a webkit setting from its name, like webkit-enable-javascript.
+    pub fn current_setting(&self, name: &str) -> Option<AppSettingsVariant> {
+        let settings = self.widgets.view.settings()?;
+        let property = name.trim_start_matches("webkit-");
+        settings.find_property(property)?;
+        let value = settings.property_value(property);
+        let value =
+            if let Ok(value) = value.get::<bool>() {
+                Value::Bool(value)
+            }
+            else if let Ok(value) = value.get::<u32>() {
+                Value::Int(value as i64)
+            }
+            else if let Ok(value) = value.get::<Option<String>>() {
+                Value::Str(value.unwrap_or_default())
+            }
+            else {
+                return None;
+            };
+        AppSettings::to_variant(name, value).ok()
+    }
+
     /// Set the cookie accept policy.
     fn set_cookie_accept(&self, cookie_accept: &CookieAcceptPolicy) {
         let cookie_manager = self.widgets.view.context()
@@ -97,7 +182,7 @@ impl WebView {
         if let Some(settings) = self.widgets.view.settings() {
             match setting {
                 CookieAccept(ref value) => self.set_cookie_accept(value),
-                HintChars(_) | HomePage(_) => (),
+                ConfigWatch(_) | HintChars(_) | HomePage(_) | OpenInTab(_) | PrivateContext(_) | TabBar(_) => (),
                 WebkitAllowFileAccessFromFileUrls(value) =>
                     settings.set_allow_file_access_from_file_urls(value),
                 WebkitAllowModalDialogs(value) =>
@@ -0,0 +1,267 @@
+/*
+ * Copyright (c) 2016-2020 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Tracking of the windows of the application.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use gtk::Window;
+
+use message_server::Privacy;
+use session::SessionTab;
+
+/// The maximum number of closed windows that can be reopened.
+const MAX_CLOSED_WINDOWS: usize = 50;
+
+/// A window that was closed.
+#[derive(Clone)]
+pub struct ClosedWindow {
+    /// The index of the tab that was shown.
+    pub current_tab: usize,
+    pub privacy: Privacy,
+    pub tabs: Vec<SessionTab>,
+    pub title: String,
+    /// The URL of the tab that was shown.
+    pub url: String,
+}
+
+/// A window that is currently opened.
+#[derive(Clone)]
+pub struct OpenedWindow {
+    /// The index of the tab shown in the window.
+    pub current_tab: usize,
+    pub id: usize,
+    pub privacy: Privacy,
+    /// The URL and back/forward history of every tab.
+    pub tabs: Vec<SessionTab>,
+    pub title: String,
+    /// The URL of the tab shown in the window.
+    pub url: String,
+    pub window: Window,
+}
+
+/// The currently opened windows, indexed by their identifier.
+/// It is maintained by the message server and shared with the windows so that they can save them and switch
+/// between them.
+#[derive(Clone)]
+pub struct OpenedWindows {
+    windows: Rc<RefCell<BTreeMap<usize, OpenedWindow>>>,
+}
+
+impl OpenedWindows {
+    /// Create an empty window registry.
+    pub fn new() -> Self {
+        OpenedWindows {
+            windows: Rc::new(RefCell::new(BTreeMap::new())),
+        }
+    }
+
+    /// Find a window from its identifier, or else from a part of its title or URL.
+    pub fn find(&self, query: &str) -> Option<OpenedWindow> {
+        let windows = self.windows.borrow();
+        if let Ok(id) = query.parse() {
+            if let Some(window) = windows.get(&id) {
+                return Some(window.clone());
+            }
+        }
+        let query = query.to_lowercase();
+        windows.values()
+            .find(|window| window.title.to_lowercase().contains(&query) || window.url.to_lowercase().contains(&query))
+            .cloned()
+    }
+
+    /// Add a window to the registry.
+    pub fn insert(&self, window: OpenedWindow) {
+        self.windows.borrow_mut().insert(window.id, window);
+    }
+
+    /// Get the opened windows, in the order they were opened.
+    pub fn list(&self) -> Vec<OpenedWindow> {
+        self.windows.borrow().values()
+            .cloned()
+            .collect()
+    }
+
+    /// Remove a window from the registry.
+    pub fn remove(&self, id: usize) {
+        self.windows.borrow_mut().remove(&id);
+    }
+
+    /// Change the tabs of a window.
+    pub fn set_tabs(&self, id: usize, current_tab: usize, tabs: Vec<SessionTab>) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.current_tab = current_tab;
+            window.tabs = tabs;
+        }
+    }
+
+    /// Change the page title of a window.
+    pub fn set_title(&self, id: usize, title: String) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.title = title;
+        }
+    }
+
+    /// Change the URL of a window.
+    pub fn set_url(&self, id: usize, url: String) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.url = url;
+        }
+    }
+}
+
+/// The stack of the recently closed windows.
+/// It is filled by the message server and shared with the windows so that they can reopen them.
+#[derive(Clone)]
+pub struct ClosedWindows {
+    windows: Rc<RefCell<Vec<ClosedWindow>>>,
+}
+
+impl ClosedWindows {
+    /// Create an empty closed window stack.
+    pub fn new() -> Self {
+        ClosedWindows {
+            windows: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    /// Get the closed windows, the most recently closed first.
+    pub fn list(&self) -> Vec<ClosedWindow> {
+        self.windows.borrow().iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// Take the most recently closed window.
+    pub fn pop(&self) -> Option<ClosedWindow> {
+        self.windows.borrow_mut().pop()
+    }
+
+    /// Add a closed window, forgetting the oldest one if there are too many.
+    pub fn push(&self, window: ClosedWindow) {
+        let mut windows = self.windows.borrow_mut();
+        windows.push(window);
+        if windows.len() > MAX_CLOSED_WINDOWS {
+            windows.remove(0);
+        }
+    }
+
+    /// Take the most recently closed window having the specified URL.
+    pub fn remove(&self, url: &str) -> Option<ClosedWindow> {
+        let mut windows = self.windows.borrow_mut();
+        let index = windows.iter().rposition(|window| window.url == url)?;
+        Some(windows.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gtk::{self, Window, WindowType};
+
+    use message_server::Privacy;
+    use super::{ClosedWindow, ClosedWindows, MAX_CLOSED_WINDOWS, OpenedWindow, OpenedWindows};
+
+    fn closed_window(url: &str) -> ClosedWindow {
+        ClosedWindow {
+            current_tab: 0,
+            privacy: Privacy::Normal,
+            tabs: vec![],
+            title: String::new(),
+            url: url.to_string(),
+        }
+    }
+
+    fn urls(windows: Vec<ClosedWindow>) -> Vec<String> {
+        windows.into_iter()
+            .map(|window| window.url)
+            .collect()
+    }
+
+    #[test]
+    fn closed_windows_order() {
+        let windows = ClosedWindows::new();
+        windows.push(closed_window("https://first.example.com/"));
+        windows.push(closed_window("https://second.example.com/"));
+        assert_eq!(urls(windows.list()), vec!["https://second.example.com/", "https://first.example.com/"]);
+        assert_eq!(windows.pop().map(|window| window.url), Some("https://second.example.com/".to_string()));
+        assert_eq!(windows.pop().map(|window| window.url), Some("https://first.example.com/".to_string()));
+        assert!(windows.pop().is_none());
+    }
+
+    #[test]
+    fn closed_windows_cap() {
+        let windows = ClosedWindows::new();
+        for index in 0..MAX_CLOSED_WINDOWS + 5 {
+            windows.push(closed_window(&format!("https://example.com/{}", index)));
+        }
+        let list = urls(windows.list());
+        assert_eq!(list.len(), MAX_CLOSED_WINDOWS);
+        assert_eq!(list[0], format!("https://example.com/{}", MAX_CLOSED_WINDOWS + 4));
+        assert_eq!(list[MAX_CLOSED_WINDOWS - 1], "https://example.com/5");
+    }
+
+    #[test]
+    fn closed_windows_remove() {
+        let windows = ClosedWindows::new();
+        windows.push(closed_window("https://example.com/"));
+        windows.push(closed_window("https://other.example.com/"));
+        windows.push(closed_window("https://example.com/"));
+        assert!(windows.remove("https://example.com/").is_some());
+        assert_eq!(urls(windows.list()), vec!["https://other.example.com/", "https://example.com/"]);
+        assert!(windows.remove("https://missing.example.com/").is_none());
+    }
+
+    #[test]
+    fn opened_windows() {
+        gtk::init().unwrap();
+
+        let windows = OpenedWindows::new();
+        let opened = [(2, "Second", "https://second.example.com/"), (0, "First", "https://first.example.com/")];
+        for &(id, title, url) in &opened {
+            windows.insert(OpenedWindow {
+                current_tab: 0,
+                id,
+                privacy: Privacy::Normal,
+                tabs: vec![],
+                title: title.to_string(),
+                url: url.to_string(),
+                window: Window::new(WindowType::Toplevel),
+            });
+        }
+        let ids: Vec<_> = windows.list().iter().map(|window| window.id).collect();
+        assert_eq!(ids, vec![0, 2]);
+
+        assert_eq!(windows.find("2").map(|window| window.id), Some(2));
+        assert_eq!(windows.find("first").map(|window| window.id), Some(0));
+        assert_eq!(windows.find("SECOND.example").map(|window| window.id), Some(2));
+        assert!(windows.find("third").is_none());
+
+        windows.set_url(0, "https://third.example.com/".to_string());
+        assert_eq!(windows.find("third").map(|window| window.id), Some(0));
+
+        windows.remove(0);
+        let ids: Vec<_> = windows.list().iter().map(|window| window.id).collect();
+        assert_eq!(ids, vec![2]);
+    }
+}